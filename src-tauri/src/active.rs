@@ -0,0 +1,26 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Number of generations currently running, shown on the dashboard.
+#[derive(Default)]
+pub struct ActiveQueries(AtomicU32);
+
+impl ActiveQueries {
+    /// Counts a query as active until the returned guard is dropped, so early
+    /// returns and `?` paths can't skip the decrement.
+    pub fn enter(&self) -> QueryGuard<'_> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        QueryGuard(&self.0)
+    }
+
+    pub fn count(&self) -> u32 {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+pub struct QueryGuard<'a>(&'a AtomicU32);
+
+impl Drop for QueryGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
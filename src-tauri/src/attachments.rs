@@ -0,0 +1,49 @@
+use std::path::Path;
+
+use crate::error::AppError;
+
+/// Files larger than this are rejected outright rather than truncated.
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Reads `paths` and formats them as a `Context:` block to put ahead of the
+/// user's message. The combined contents are cut to `limit` bytes with a
+/// notice. Fails listing every path that is unreadable, binary or oversized.
+pub fn read_context(paths: &[String], limit: usize) -> Result<String, AppError> {
+    let mut files = Vec::new();
+    let mut failures = Vec::new();
+    for path in paths {
+        match read_text_file(Path::new(path)) {
+            Ok(text) => files.push((path, text)),
+            Err(reason) => failures.push(format!("{} ({})", path, reason)),
+        }
+    }
+    if !failures.is_empty() {
+        return Err(AppError::InvalidParams(format!("could not attach {}", failures.join(", "))));
+    }
+
+    let mut context = String::new();
+    for (path, text) in files {
+        context.push_str(&format!("--- {} ---\n{}\n", path, text));
+    }
+    if context.len() > limit {
+        let mut end = limit;
+        while !context.is_char_boundary(end) {
+            end -= 1;
+        }
+        context.truncate(end);
+        context.push_str(&format!("\n[context truncated to {} bytes]\n", limit));
+    }
+    Ok(format!("Context:\n{}\n", context))
+}
+
+fn read_text_file(path: &Path) -> Result<String, String> {
+    let size = std::fs::metadata(path).map_err(|e| e.to_string())?.len();
+    if size > MAX_FILE_BYTES {
+        return Err(format!("larger than {} bytes", MAX_FILE_BYTES));
+    }
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    if bytes.contains(&0) {
+        return Err("binary file".into());
+    }
+    String::from_utf8(bytes).map_err(|_| "not valid UTF-8 text".to_string())
+}
@@ -0,0 +1,42 @@
+use battery::units::ratio::percent;
+use battery::units::time::second;
+
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatteryState {
+    Charging,
+    Discharging,
+    Full,
+    /// Plugged in but neither charging nor full, or not reported.
+    Unknown,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct BatteryInfo {
+    /// Remaining charge, 0–100.
+    pub percentage: f32,
+    pub state: BatteryState,
+    /// Estimated from the current draw; only set while discharging.
+    pub time_to_empty_secs: Option<u64>,
+}
+
+/// The first battery found, or `None` on machines without one (or where
+/// the platform can't be queried).
+pub fn read() -> Option<BatteryInfo> {
+    let manager = battery::Manager::new().ok()?;
+    let battery = manager.batteries().ok()?.flatten().next()?;
+    let state = match battery.state() {
+        battery::State::Charging => BatteryState::Charging,
+        battery::State::Discharging => BatteryState::Discharging,
+        battery::State::Full => BatteryState::Full,
+        _ => BatteryState::Unknown,
+    };
+    let time_to_empty_secs = match state {
+        BatteryState::Discharging => battery.time_to_empty().map(|t| t.get::<second>() as u64),
+        _ => None,
+    };
+    Some(BatteryInfo {
+        percentage: battery.state_of_charge().get::<percent>().clamp(0.0, 100.0),
+        state,
+        time_to_empty_secs,
+    })
+}
@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+
+use tauri::Manager;
+
+use crate::active::ActiveQueries;
+use crate::config::ConfigStore;
+use crate::context;
+use crate::error::AppError;
+use crate::http::HttpClient;
+use crate::providers::{self, GenerationParams, ProviderConfig};
+use crate::rate_limit::RateLimiter;
+use crate::retry;
+use crate::tokens;
+
+/// Upper bound on `runs`, so a typo can't queue hours of requests.
+const MAX_RUNS: u32 = 50;
+
+#[derive(serde::Serialize, Debug, Default)]
+pub struct BenchmarkResult {
+    /// Timed runs that finished; the warmup run isn't counted.
+    runs_completed: u32,
+    min_tokens_per_sec: f64,
+    median_tokens_per_sec: f64,
+    max_tokens_per_sec: f64,
+    avg_latency_ms: u64,
+    /// Why the benchmark stopped early. The stats cover the runs before it.
+    error: Option<AppError>,
+}
+
+impl BenchmarkResult {
+    fn from_runs(runs: &[(usize, Duration)], error: Option<AppError>) -> Self {
+        if runs.is_empty() {
+            return BenchmarkResult { error, ..Default::default() };
+        }
+        let mut rates: Vec<f64> = runs
+            .iter()
+            .map(|(tokens, elapsed)| *tokens as f64 / elapsed.as_secs_f64().max(f64::EPSILON))
+            .collect();
+        rates.sort_by(f64::total_cmp);
+        let mid = rates.len() / 2;
+        let median = if rates.len() % 2 == 0 { (rates[mid - 1] + rates[mid]) / 2.0 } else { rates[mid] };
+        let total: Duration = runs.iter().map(|(_, elapsed)| *elapsed).sum();
+        BenchmarkResult {
+            runs_completed: runs.len() as u32,
+            min_tokens_per_sec: rates[0],
+            median_tokens_per_sec: median,
+            max_tokens_per_sec: rates[rates.len() - 1],
+            avg_latency_ms: (total / runs.len() as u32).as_millis() as u64,
+            error,
+        }
+    }
+}
+
+/// Answers `prompt` with `model` from the default provider `runs` times,
+/// plus one untimed warmup run first, and reports completion tokens per
+/// second of wall time. Each run waits its turn with the rate limiter. The
+/// first failure ends the benchmark; it comes back in `error` alongside the
+/// stats of the runs that finished.
+#[tauri::command]
+#[tracing::instrument(skip(app, prompt), err)]
+pub async fn benchmark_model(app: tauri::AppHandle, model: String, prompt: String, runs: u32) -> Result<BenchmarkResult, AppError> {
+    if prompt.trim().is_empty() {
+        return Err(AppError::InvalidParams("prompt must not be empty".into()));
+    }
+    if !(1..=MAX_RUNS).contains(&runs) {
+        return Err(AppError::InvalidParams(format!("runs must be between 1 and {}", MAX_RUNS)));
+    }
+    let config = app.state::<ConfigStore>().get()?;
+    if config.offline_mode {
+        return Err(AppError::InvalidParams("benchmarks need a provider; offline mode is on".into()));
+    }
+    let http = app.state::<HttpClient>();
+    let provider_config = app.state::<ProviderConfig>();
+    let provider = providers::build(provider_config.default_kind, &provider_config, http.get()?, &model)?;
+    let messages = context::build_messages(None, &[], &prompt);
+    let params = GenerationParams::default();
+    let active = app.state::<ActiveQueries>();
+    let _active = active.enter();
+
+    // The warmup may meet a cold model, so only it is retried.
+    app.state::<RateLimiter>().acquire().await?;
+    if let Err(e) = retry::with_backoff(config.max_retries, || provider.chat(&messages, &params)).await {
+        return Ok(BenchmarkResult::from_runs(&[], Some(http.explain(e.into()))));
+    }
+
+    let mut timed = Vec::with_capacity(runs as usize);
+    let mut error = None;
+    for _ in 0..runs {
+        if let Err(e) = app.state::<RateLimiter>().acquire().await {
+            error = Some(e);
+            break;
+        }
+        let started = Instant::now();
+        match provider.chat(&messages, &params).await {
+            Ok(reply) => timed.push((tokens::count_tokens(&reply, provider.model()), started.elapsed())),
+            Err(e) => {
+                error = Some(http.explain(e.into()));
+                break;
+            }
+        }
+    }
+    let result = BenchmarkResult::from_runs(&timed, error);
+    tracing::info!(
+        runs = result.runs_completed,
+        median_tokens_per_sec = result.median_tokens_per_sec,
+        avg_latency_ms = result.avg_latency_ms,
+        "benchmark finished"
+    );
+    Ok(result)
+}
@@ -0,0 +1,77 @@
+use std::sync::Mutex;
+
+use regex::RegexSet;
+use tauri::Manager;
+
+use crate::config::ConfigStore;
+use crate::error::AppError;
+
+/// Prompts matching any of `patterns` are refused before they reach a
+/// provider. Off by default.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct BlocklistConfig {
+    pub enabled: bool,
+    /// Regular expressions in `regex` crate syntax, matched anywhere in the
+    /// prompt. Use `(?i)` for case-insensitive patterns.
+    pub patterns: Vec<String>,
+}
+
+/// The configured patterns, compiled once. `None` while the blocklist is
+/// disabled or empty.
+pub struct Blocklist(Mutex<Option<(RegexSet, Vec<String>)>>);
+
+impl Blocklist {
+    pub fn new(config: &BlocklistConfig) -> Result<Self, AppError> {
+        Ok(Blocklist(Mutex::new(compile(config)?)))
+    }
+
+    pub fn set(&self, config: &BlocklistConfig) -> Result<(), AppError> {
+        *self.0.lock()? = compile(config)?;
+        Ok(())
+    }
+
+    /// Fails with `AppError::Blocked` naming the first pattern `prompt`
+    /// matches.
+    pub fn check(&self, prompt: &str) -> Result<(), AppError> {
+        let compiled = self.0.lock()?;
+        let Some((set, patterns)) = compiled.as_ref() else {
+            return Ok(());
+        };
+        match set.matches(prompt).iter().next() {
+            Some(i) => Err(AppError::Blocked { pattern: patterns[i].clone() }),
+            None => Ok(()),
+        }
+    }
+}
+
+fn compile(config: &BlocklistConfig) -> Result<Option<(RegexSet, Vec<String>)>, AppError> {
+    validate(&config.patterns)?;
+    if !config.enabled || config.patterns.is_empty() {
+        return Ok(None);
+    }
+    let set = RegexSet::new(&config.patterns).map_err(|e| AppError::InvalidParams(e.to_string()))?;
+    Ok(Some((set, config.patterns.clone())))
+}
+
+/// Checks each pattern compiles, naming the first that doesn't.
+fn validate(patterns: &[String]) -> Result<(), AppError> {
+    for pattern in patterns {
+        regex::Regex::new(pattern)
+            .map_err(|e| AppError::InvalidParams(format!("invalid blocklist pattern {:?}: {}", pattern, e)))?;
+    }
+    Ok(())
+}
+
+/// Replaces the blocklist patterns, keeping whether it is enabled.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(patterns = patterns.len()), err)]
+pub fn set_blocklist(app: tauri::AppHandle, patterns: Vec<String>) -> Result<(), AppError> {
+    let config = app.state::<ConfigStore>();
+    let blocklist = BlocklistConfig {
+        patterns,
+        ..config.get()?.blocklist
+    };
+    app.state::<Blocklist>().set(&blocklist)?;
+    config.update(|c| c.blocklist = blocklist)
+}
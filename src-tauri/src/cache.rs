@@ -0,0 +1,59 @@
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+
+use lru::LruCache;
+
+use crate::error::AppError;
+use crate::providers::GenerationParams;
+
+/// `(model, normalized prompt, serialized params)`. Params are keyed by their
+/// JSON form since floats can't be hashed directly.
+type CacheKey = (String, String, String);
+
+/// Replies to recently seen prompts, so re-sending an identical request
+/// doesn't cost another API call. A capacity of 0 disables caching.
+pub struct ResponseCache(Mutex<Option<LruCache<CacheKey, String>>>);
+
+impl ResponseCache {
+    pub fn new(capacity: usize) -> Self {
+        ResponseCache(Mutex::new(NonZeroUsize::new(capacity).map(LruCache::new)))
+    }
+
+    pub fn get(&self, model: &str, prompt: &str, params: &GenerationParams) -> Result<Option<String>, AppError> {
+        let key = key(model, prompt, params)?;
+        Ok(self.0.lock()?.as_mut().and_then(|cache| cache.get(&key).cloned()))
+    }
+
+    pub fn put(&self, model: &str, prompt: &str, params: &GenerationParams, reply: &str) -> Result<(), AppError> {
+        let key = key(model, prompt, params)?;
+        if let Some(cache) = self.0.lock()?.as_mut() {
+            cache.put(key, reply.to_string());
+        }
+        Ok(())
+    }
+
+    /// Changes the capacity, evicting the oldest entries if it shrinks.
+    pub fn resize(&self, capacity: usize) -> Result<(), AppError> {
+        let mut cache = self.0.lock()?;
+        match (NonZeroUsize::new(capacity), cache.as_mut()) {
+            (Some(capacity), Some(existing)) => existing.resize(capacity),
+            (capacity, _) => *cache = capacity.map(LruCache::new),
+        }
+        Ok(())
+    }
+
+    pub fn clear(&self) -> Result<(), AppError> {
+        if let Some(cache) = self.0.lock()?.as_mut() {
+            cache.clear();
+        }
+        Ok(())
+    }
+}
+
+/// Collapses runs of whitespace so prompts differing only in spacing share an
+/// entry.
+fn key(model: &str, prompt: &str, params: &GenerationParams) -> Result<CacheKey, AppError> {
+    let prompt = prompt.split_whitespace().collect::<Vec<_>>().join(" ");
+    let params = serde_json::to_string(params).map_err(|e| AppError::Parse(e.to_string()))?;
+    Ok((model.to_string(), prompt, params))
+}
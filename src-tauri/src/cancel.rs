@@ -0,0 +1,53 @@
+use dashmap::DashMap;
+use tokio_util::sync::CancellationToken;
+
+/// Cancellation tokens for generations that are still running, keyed by the
+/// request id the frontend supplied.
+#[derive(Default)]
+pub struct InFlightRequests(DashMap<String, CancellationToken>);
+
+impl InFlightRequests {
+    /// Registers `id` and returns a guard holding its token. The entry is
+    /// removed when the guard drops, whether the request finished, failed or
+    /// was cancelled.
+    pub fn register(&self, id: String) -> Registration<'_> {
+        let token = CancellationToken::new();
+        self.0.insert(id.clone(), token.clone());
+        Registration { requests: self, id, token }
+    }
+
+    /// Cancels the request registered under `id`. Returns `false` if no such
+    /// request is running.
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.0.get(id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Cancellation tokens for running batches, keyed by batch id. Kept apart
+/// from `InFlightRequests` so batch and request ids can't collide.
+#[derive(Default)]
+pub struct RunningBatches(pub InFlightRequests);
+
+pub struct Registration<'a> {
+    requests: &'a InFlightRequests,
+    id: String,
+    token: CancellationToken,
+}
+
+impl Registration<'_> {
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        self.requests.0.remove(&self.id);
+    }
+}
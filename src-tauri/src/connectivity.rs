@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::AppError;
+
+/// How long a check result is reused before the host is probed again.
+const CHECK_TTL: Duration = Duration::from_secs(5);
+/// A host that doesn't answer within this is treated as unreachable.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Recent reachability results, keyed by origin (`scheme://host:port`), so a
+/// burst of requests costs at most one probe per host.
+#[derive(Default)]
+pub struct Connectivity(Mutex<HashMap<String, (Instant, bool)>>);
+
+impl Connectivity {
+    /// Sends a quick `HEAD` to the origin of `endpoint` unless it was checked
+    /// recently. Any HTTP response counts as reachable; only a failure to
+    /// connect or a timeout gives `AppError::Offline`.
+    pub async fn ensure_reachable(&self, client: &reqwest::Client, endpoint: &str) -> Result<(), AppError> {
+        let Ok(url) = reqwest::Url::parse(endpoint) else {
+            // Malformed endpoints fail with a clearer error on the real request.
+            return Ok(());
+        };
+        let origin = url.origin().ascii_serialization();
+
+        let cached = self.0.lock()?.get(&origin).copied();
+        let reachable = match cached {
+            Some((checked_at, reachable)) if checked_at.elapsed() < CHECK_TTL => reachable,
+            _ => {
+                let reachable = client.head(&origin).timeout(CHECK_TIMEOUT).send().await.is_ok();
+                self.0.lock()?.insert(origin.clone(), (Instant::now(), reachable));
+                reachable
+            }
+        };
+        if reachable {
+            Ok(())
+        } else {
+            Err(AppError::Offline(origin))
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use crate::providers::PromptMessage;
+use crate::{ChatMessage, Role};
+
+/// The chat to send for a new message: the conversation's system prompt, if
+/// any, then prior turns, then the message itself.
+pub fn build_messages(system_prompt: Option<&str>, history: &[ChatMessage], message: &str) -> Vec<PromptMessage> {
+    let mut messages = Vec::with_capacity(history.len() + 2);
+    if let Some(system_prompt) = system_prompt {
+        messages.push(PromptMessage::new(Role::System, system_prompt));
+    }
+    for turn in history {
+        messages.push(PromptMessage::new(turn.sender, &turn.content));
+    }
+    messages.push(PromptMessage::new(Role::User, message));
+    messages
+}
+
+/// Drops the oldest turns from `messages` until the prompt made from them
+/// fits in `budget` tokens as measured by `count`. A leading system prompt
+/// and the final message are always kept, even if they alone are over
+/// budget. Returns whether anything was dropped.
+pub fn fit_to_budget(messages: &mut Vec<PromptMessage>, budget: usize, count: impl Fn(&str) -> usize) -> bool {
+    let line_tokens: Vec<usize> = messages.iter().map(|m| count(&format_line(m))).collect();
+    let mut total = line_tokens.iter().sum::<usize>() + count(OPEN_TURN);
+    let first = usize::from(messages.first().is_some_and(|m| m.role == Role::System));
+    let mut drop = 0;
+    while total > budget && first + drop + 1 < messages.len() {
+        total -= line_tokens[first + drop];
+        drop += 1;
+    }
+    messages.drain(first..first + drop);
+    drop > 0
+}
+
+const OPEN_TURN: &str = "Assistant:";
+
+fn format_line(message: &PromptMessage) -> String {
+    format!("{}: {}\n", message.role.label(), message.content)
+}
+
+/// Flattens `messages` into alternating `User:` / `Assistant:` lines, ending
+/// with an open `Assistant:` turn for the model to complete, for providers
+/// that take a single prompt string.
+pub fn format_prompt(messages: &[PromptMessage]) -> String {
+    let mut prompt = String::new();
+    for message in messages {
+        prompt.push_str(&format_line(message));
+    }
+    prompt.push_str(OPEN_TURN);
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(text: &str) -> usize {
+        text.split_whitespace().count()
+    }
+
+    /// A system prompt, `turns` alternating user/assistant messages of ten
+    /// words each, then the new message.
+    fn long_chat(turns: usize) -> Vec<PromptMessage> {
+        let mut messages = vec![PromptMessage::new(Role::System, "be brief")];
+        for i in 0..turns {
+            let role = if i % 2 == 0 { Role::User } else { Role::Assistant };
+            messages.push(PromptMessage::new(role, &format!("turn {} {}", i, "word ".repeat(8))));
+        }
+        messages.push(PromptMessage::new(Role::User, "latest question"));
+        messages
+    }
+
+    #[test]
+    fn leaves_a_chat_that_fits_alone() {
+        let mut messages = long_chat(4);
+        let before = messages.len();
+        assert!(!fit_to_budget(&mut messages, 1000, words));
+        assert_eq!(messages.len(), before);
+    }
+
+    #[test]
+    fn drops_oldest_turns_first() {
+        let mut messages = long_chat(100);
+        assert!(fit_to_budget(&mut messages, 200, words));
+        assert!(words(&format_prompt(&messages)) <= 200);
+        assert_eq!(messages[0].role, Role::System);
+        assert_eq!(messages.last().unwrap().content, "latest question");
+        // What survives is the most recent stretch of the history.
+        assert!(messages[messages.len() - 2].content.starts_with("turn 99 "));
+        assert!(!messages.iter().any(|m| m.content.starts_with("turn 0 ")));
+    }
+
+    #[test]
+    fn keeps_system_prompt_and_message_when_nothing_fits() {
+        let mut messages = long_chat(10);
+        assert!(fit_to_budget(&mut messages, 1, words));
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].role, Role::System);
+        assert_eq!(messages[1].content, "latest question");
+    }
+
+    #[test]
+    fn works_without_a_system_prompt() {
+        let mut messages = long_chat(20);
+        messages.remove(0);
+        assert!(fit_to_budget(&mut messages, 50, words));
+        assert!(words(&format_prompt(&messages)) <= 50);
+        assert_eq!(messages.last().unwrap().content, "latest question");
+    }
+}
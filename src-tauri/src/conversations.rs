@@ -0,0 +1,63 @@
+use tauri::Manager;
+
+use crate::db;
+use crate::providers::{AiProvider, GenerationParams};
+
+const MAX_TITLE_WORDS: usize = 6;
+const FALLBACK_TITLE_CHARS: usize = 40;
+
+/// Titles an untitled conversation in the background after its first
+/// exchange, so the reply isn't held up by a second model call.
+pub fn spawn_title_generation(
+    app: tauri::AppHandle,
+    provider: Box<dyn AiProvider>,
+    conversation_id: String,
+    user_message: String,
+    reply: String,
+) {
+    tauri::async_runtime::spawn(async move {
+        let title = generate_title(provider.as_ref(), &user_message, &reply).await;
+        let db = app.state::<db::Db>();
+        if let Ok(conn) = db.0.lock() {
+            let _ = db::set_conversation_title(&conn, &conversation_id, &title);
+        };
+    });
+}
+
+/// Asks the model for a title of at most six words, falling back to the
+/// start of the user's message if the call fails or yields nothing usable.
+async fn generate_title(provider: &dyn AiProvider, user_message: &str, reply: &str) -> String {
+    let prompt = format!(
+        "Summarize this exchange as a title of at most {} words. Reply with the title only.\n\nUser: {}\nAssistant: {}\nTitle:",
+        MAX_TITLE_WORDS, user_message, reply
+    );
+    let params = GenerationParams {
+        temperature: Some(0.3),
+        max_new_tokens: Some(16),
+        ..Default::default()
+    };
+    provider
+        .generate(&prompt, &params)
+        .await
+        .ok()
+        .and_then(|raw| clean_title(&raw))
+        .unwrap_or_else(|| fallback_title(user_message))
+}
+
+/// First line of the model output, stripped of quotes and trailing
+/// punctuation and capped at six words.
+fn clean_title(raw: &str) -> Option<String> {
+    let line = raw.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let line = line.trim_start_matches("Title:").trim_matches(|c: char| c == '"' || c == '\'' || c.is_whitespace());
+    let title = line
+        .split_whitespace()
+        .take(MAX_TITLE_WORDS)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let title = title.trim_end_matches(|c: char| c.is_ascii_punctuation()).to_string();
+    (!title.is_empty()).then_some(title)
+}
+
+fn fallback_title(user_message: &str) -> String {
+    user_message.trim().chars().take(FALLBACK_TITLE_CHARS).collect()
+}
@@ -0,0 +1,48 @@
+use crate::error::AppError;
+
+const SERVICE: &str = "aios";
+const HF_TOKEN_USER: &str = "hf_token";
+
+fn entry() -> Result<keyring::Entry, AppError> {
+    keyring::Entry::new(SERVICE, HF_TOKEN_USER).map_err(|e| AppError::Credentials(e.to_string()))
+}
+
+/// The Hugging Face token from the OS credential store, falling back to the
+/// `HF_TOKEN` env var when none has been saved there.
+pub fn hf_token() -> Option<String> {
+    entry()
+        .ok()
+        .and_then(|entry| entry.get_password().ok())
+        .or_else(|| std::env::var("HF_TOKEN").ok())
+        .filter(|token| !token.is_empty())
+}
+
+/// The Hugging Face token, for commands about to call the API. Everything
+/// else (history, performance, offline mode) must work without one, so only
+/// network-bound paths should call this.
+pub fn require_token() -> Result<String, AppError> {
+    hf_token().ok_or(AppError::MissingToken)
+}
+
+/// Saves the Hugging Face token to the OS credential store (Keychain,
+/// Credential Manager or Secret Service), which keeps it encrypted at rest.
+/// The token is never sent back to the frontend.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub fn set_api_token(token: String) -> Result<(), AppError> {
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(AppError::InvalidParams("token must not be empty".into()));
+    }
+    entry()?.set_password(token).map_err(|e| AppError::Credentials(e.to_string()))
+}
+
+/// Removes the saved token. `HF_TOKEN`, if set, applies again afterwards.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub fn clear_api_token() -> Result<(), AppError> {
+    match entry()?.delete_password() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(AppError::Credentials(e.to_string())),
+    }
+}
@@ -0,0 +1,84 @@
+use std::path::{Path, PathBuf};
+
+use sysinfo::{DiskExt, System, SystemExt};
+
+use crate::error::AppError;
+
+/// Where the database, config and logs live: `AIOS_DATA_DIR` if set,
+/// otherwise the platform's app data directory.
+pub struct DataDir(pub PathBuf);
+
+impl DataDir {
+    /// Resolves the directory, creating it if needed, and checks that it can
+    /// be written to.
+    pub fn resolve(default: Option<PathBuf>) -> Result<Self, AppError> {
+        let path = std::env::var_os("AIOS_DATA_DIR")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or(default)
+            .ok_or_else(|| AppError::InvalidParams("could not resolve the app data directory".into()))?;
+        std::fs::create_dir_all(&path).map_err(|e| not_writable(&path, e))?;
+        check_writable(&path)?;
+        Ok(DataDir(path))
+    }
+
+    /// Whether the directory can still be written to, e.g. after the disk
+    /// was remounted read-only.
+    pub fn is_writable(&self) -> bool {
+        check_writable(&self.0).is_ok()
+    }
+}
+
+fn check_writable(path: &Path) -> Result<(), AppError> {
+    let probe = path.join(".write-test");
+    std::fs::write(&probe, b"").map_err(|e| not_writable(path, e))?;
+    let _ = std::fs::remove_file(&probe);
+    Ok(())
+}
+
+fn not_writable(path: &Path, err: std::io::Error) -> AppError {
+    AppError::Io(std::io::Error::new(
+        err.kind(),
+        format!("data directory {} is not writable: {}", path.display(), err),
+    ))
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub fn get_data_dir(data_dir: tauri::State<'_, DataDir>) -> Result<String, AppError> {
+    Ok(data_dir.0.display().to_string())
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct DiskSpace {
+    pub mount_point: String,
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+}
+
+/// Space on the filesystem holding the data directory: the disk whose mount
+/// point is the longest prefix of the (canonicalized) path.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub fn get_data_dir_space(data_dir: tauri::State<'_, DataDir>) -> Result<DiskSpace, AppError> {
+    let path = data_dir.0.canonicalize()?;
+    let mut sys = System::new();
+    sys.refresh_disks_list();
+    sys.refresh_disks();
+    let disk = sys
+        .disks()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().components().count())
+        .ok_or_else(|| {
+            AppError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no mounted disk contains {}", path.display()),
+            ))
+        })?;
+    Ok(DiskSpace {
+        mount_point: disk.mount_point().display().to_string(),
+        total_bytes: disk.total_space(),
+        available_bytes: disk.available_space(),
+    })
+}
@@ -0,0 +1,870 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use crate::migrations;
+use crate::providers::GenerationParams;
+use crate::{ChatMessage, MessageMeta};
+
+/// Shared SQLite connection, opened once at startup and handed to commands
+/// through Tauri managed state.
+pub struct Db(pub Mutex<Connection>);
+
+/// Opens the database at `path` and brings its schema up to date.
+pub fn open(path: &Path) -> rusqlite::Result<Connection> {
+    let mut conn = Connection::open(path)?;
+    migrations::run(&mut conn)?;
+    Ok(conn)
+}
+
+const MESSAGE_COLUMNS: &str = "id, content, sender, timestamp, conversation_id, pinned, content_file";
+
+/// Reads a message as stored: one with a `content_file` comes back as its
+/// preview, marked `truncated` until `sidecar::rehydrate` fills it in.
+fn message_from_row(row: &Row) -> rusqlite::Result<ChatMessage> {
+    let content_file: Option<String> = row.get(6)?;
+    Ok(ChatMessage {
+        id: row.get(0)?,
+        content: row.get(1)?,
+        sender: row.get(2)?,
+        timestamp: row.get(3)?,
+        conversation_id: row.get(4)?,
+        pinned: row.get(5)?,
+        snippet: None,
+        meta: None,
+        truncated: content_file.is_some(),
+        content_file,
+    })
+}
+
+/// The `message_meta` columns that `load_messages` joins in after
+/// `MESSAGE_COLUMNS`.
+const META_COLUMNS: &str = "meta.model, meta.temperature, meta.prompt_tokens, meta.completion_tokens, meta.latency_ms";
+
+fn message_with_meta_from_row(row: &Row) -> rusqlite::Result<ChatMessage> {
+    let mut message = message_from_row(row)?;
+    let meta = MessageMeta {
+        model: row.get(7)?,
+        temperature: row.get(8)?,
+        prompt_tokens: row.get(9)?,
+        completion_tokens: row.get(10)?,
+        latency_ms: row.get(11)?,
+    };
+    message.meta = meta.model.is_some().then_some(meta);
+    Ok(message)
+}
+
+pub fn insert_message_meta(conn: &Connection, message_id: &str, meta: &MessageMeta) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO message_meta (message_id, model, temperature, prompt_tokens, completion_tokens, latency_ms)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            message_id,
+            meta.model,
+            meta.temperature,
+            meta.prompt_tokens.map(|n| n as i64),
+            meta.completion_tokens.map(|n| n as i64),
+            meta.latency_ms.map(|n| n as i64)
+        ],
+    )?;
+    Ok(())
+}
+
+/// Saves a message and bumps its conversation's `updated_at`, creating the
+/// conversation row on first use.
+pub fn insert_message(conn: &Connection, message: &ChatMessage) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO messages (id, content, sender, timestamp, conversation_id, pinned, content_file)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            message.id,
+            message.content,
+            message.sender,
+            message.timestamp,
+            message.conversation_id,
+            message.pinned,
+            message.content_file
+        ],
+    )?;
+    conn.execute(
+        "INSERT INTO conversations (id, updated_at) VALUES (?1, ?2)
+         ON CONFLICT (id) DO UPDATE SET updated_at = excluded.updated_at",
+        params![message.conversation_id, message.timestamp],
+    )?;
+    Ok(())
+}
+
+/// Loads saved messages oldest first, optionally limited to one conversation,
+/// with the generation metadata of assistant replies.
+pub fn load_messages(conn: &Connection, conversation_id: Option<&str>) -> rusqlite::Result<Vec<ChatMessage>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, {} FROM messages LEFT JOIN message_meta meta ON meta.message_id = messages.id
+         WHERE (?1 IS NULL OR conversation_id = ?1) AND deleted_at IS NULL
+         ORDER BY timestamp, messages.rowid",
+        MESSAGE_COLUMNS, META_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![conversation_id], message_with_meta_from_row)?;
+    rows.collect()
+}
+
+/// Loads the last `limit` messages of a conversation, oldest first.
+pub fn load_recent_messages(conn: &Connection, conversation_id: &str, limit: usize) -> rusqlite::Result<Vec<ChatMessage>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM messages
+         WHERE conversation_id = ?1 AND deleted_at IS NULL
+         ORDER BY timestamp DESC, rowid DESC LIMIT ?2",
+        MESSAGE_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![conversation_id, limit as i64], message_from_row)?;
+    let mut messages = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+    messages.reverse();
+    Ok(messages)
+}
+
+/// Deletes one message, returning how many rows were removed (0 or 1).
+pub fn delete_message(conn: &Connection, id: &str) -> rusqlite::Result<usize> {
+    conn.execute("DELETE FROM messages WHERE id = ?1", params![id])
+}
+
+/// Inserts `messages` into `conversation_id` under fresh ids, in one
+/// transaction so a failure leaves nothing behind.
+pub fn import_messages(conn: &Connection, conversation_id: &str, messages: Vec<ChatMessage>) -> rusqlite::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    insert_copies(&tx, conversation_id, messages)?;
+    tx.commit()
+}
+
+fn insert_copies(conn: &Connection, conversation_id: &str, messages: Vec<ChatMessage>) -> rusqlite::Result<()> {
+    for message in messages {
+        insert_message(
+            conn,
+            &ChatMessage {
+                id: uuid::Uuid::new_v4().to_string(),
+                conversation_id: conversation_id.to_string(),
+                snippet: None,
+                ..message
+            },
+        )?;
+    }
+    Ok(())
+}
+
+/// Calls `f` with each message of a conversation, oldest first, reading rows
+/// as it goes instead of loading them all. Stops at the first error.
+pub fn for_each_message<E: From<rusqlite::Error>>(
+    conn: &Connection,
+    conversation_id: &str,
+    mut f: impl FnMut(&ChatMessage) -> Result<(), E>,
+) -> Result<(), E> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM messages WHERE conversation_id = ?1 AND deleted_at IS NULL ORDER BY timestamp, rowid",
+        MESSAGE_COLUMNS
+    ))?;
+    let mut rows = stmt.query(params![conversation_id])?;
+    while let Some(row) = rows.next()? {
+        f(&message_from_row(row)?)?;
+    }
+    Ok(())
+}
+
+/// Restores conversations from a backup under their original ids, with new
+/// message ids. Conversations whose id already exists are left alone. Runs
+/// in one transaction; returns how many were imported and how many skipped.
+pub fn import_conversations(
+    conn: &Connection,
+    conversations: Vec<(ConversationSummary, Vec<ChatMessage>)>,
+) -> rusqlite::Result<(usize, usize)> {
+    let tx = conn.unchecked_transaction()?;
+    let (mut imported, mut skipped) = (0, 0);
+    for (conversation, messages) in conversations {
+        let exists: bool = tx.query_row(
+            "SELECT EXISTS (SELECT 1 FROM conversations WHERE id = ?1)",
+            params![conversation.id],
+            |row| row.get(0),
+        )?;
+        if exists {
+            skipped += 1;
+            continue;
+        }
+        insert_copies(&tx, &conversation.id, messages)?;
+        tx.execute(
+            "INSERT INTO conversations (id, title, updated_at, parent_id) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (id) DO UPDATE SET title = excluded.title, updated_at = excluded.updated_at,
+                parent_id = excluded.parent_id",
+            params![conversation.id, conversation.title, conversation.updated_at, conversation.parent_id],
+        )?;
+        imported += 1;
+    }
+    tx.commit()?;
+    Ok((imported, skipped))
+}
+
+/// Hides a message until it is restored with `undo_delete` or purged.
+/// Returns 0 if there is no such message or it is already deleted.
+pub fn soft_delete_message(conn: &Connection, id: &str, deleted_at: &str) -> rusqlite::Result<usize> {
+    conn.execute(
+        "UPDATE messages SET deleted_at = ?2 WHERE id = ?1 AND deleted_at IS NULL",
+        params![id, deleted_at],
+    )
+}
+
+/// Restores a soft-deleted message, returning 0 if it wasn't deleted.
+pub fn undo_delete(conn: &Connection, id: &str) -> rusqlite::Result<usize> {
+    conn.execute(
+        "UPDATE messages SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL",
+        params![id],
+    )
+}
+
+/// Permanently removes messages soft-deleted before `cutoff` (epoch millis).
+pub fn purge_deleted(conn: &Connection, cutoff: i64) -> rusqlite::Result<usize> {
+    conn.execute(
+        "DELETE FROM messages WHERE deleted_at IS NOT NULL AND CAST(deleted_at AS INTEGER) < ?1",
+        params![cutoff],
+    )
+}
+
+/// Rebuilds the database file without free pages, then folds the WAL (if
+/// any) back into it and truncates it.
+pub fn compact(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch("VACUUM")?;
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+}
+
+/// Bytes on disk for the database, counting its WAL file; 0 for an
+/// in-memory database.
+pub fn file_size(conn: &Connection) -> std::io::Result<u64> {
+    let Some(path) = conn.path().filter(|path| !path.is_empty()) else {
+        return Ok(0);
+    };
+    let wal = std::fs::metadata(format!("{}-wal", path)).map_or(0, |meta| meta.len());
+    Ok(std::fs::metadata(path)?.len() + wal)
+}
+
+/// Deletes every message in `conversation_id`, or all messages when `None`.
+/// Pinned messages are only deleted when `include_pinned` is set.
+pub fn clear_messages(conn: &Connection, conversation_id: Option<&str>, include_pinned: bool) -> rusqlite::Result<usize> {
+    conn.execute(
+        "DELETE FROM messages WHERE (?1 IS NULL OR conversation_id = ?1) AND (?2 OR NOT pinned)",
+        params![conversation_id, include_pinned],
+    )
+}
+
+/// Removes a conversation and everything tied to it in one transaction: its
+/// messages (soft-deleted ones too), whose search index entries, embeddings
+/// and metadata go with them through triggers, plus its system prompt,
+/// settings, draft, summary and queued prompts. Branches of it are kept but
+/// lose their parent. Returns how many messages were removed.
+pub fn delete_conversation(conn: &Connection, conversation_id: &str) -> rusqlite::Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let removed = tx.execute("DELETE FROM messages WHERE conversation_id = ?1", params![conversation_id])?;
+    for table in ["system_prompts", "conversation_settings", "drafts", "conversation_summaries", "outbox"] {
+        tx.execute(&format!("DELETE FROM {} WHERE conversation_id = ?1", table), params![conversation_id])?;
+    }
+    tx.execute("UPDATE conversations SET parent_id = NULL WHERE parent_id = ?1", params![conversation_id])?;
+    tx.execute("DELETE FROM conversations WHERE id = ?1", params![conversation_id])?;
+    tx.commit()?;
+    Ok(removed)
+}
+
+/// Pins or unpins a message, returning it as updated, or `None` if there is
+/// no message with that id.
+pub fn set_pinned(conn: &Connection, id: &str, pinned: bool) -> rusqlite::Result<Option<ChatMessage>> {
+    conn.execute("UPDATE messages SET pinned = ?2 WHERE id = ?1", params![id, pinned])?;
+    get_message(conn, id)
+}
+
+/// The message with this id, unless there is none or it was deleted.
+pub fn get_message(conn: &Connection, id: &str) -> rusqlite::Result<Option<ChatMessage>> {
+    conn.query_row(
+        &format!("SELECT {} FROM messages WHERE id = ?1 AND deleted_at IS NULL", MESSAGE_COLUMNS),
+        params![id],
+        message_from_row,
+    )
+    .optional()
+}
+
+/// Pinned messages across all conversations, oldest first.
+pub fn load_pinned_messages(conn: &Connection) -> rusqlite::Result<Vec<ChatMessage>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM messages WHERE pinned AND deleted_at IS NULL ORDER BY timestamp, rowid",
+        MESSAGE_COLUMNS
+    ))?;
+    let rows = stmt.query_map([], message_from_row)?;
+    rows.collect()
+}
+
+/// Full-text search over message content, best matches first. Each result
+/// carries a snippet with the matched terms wrapped in `<mark>` tags.
+pub fn search_messages(conn: &Connection, query: &str, limit: u32) -> rusqlite::Result<Vec<ChatMessage>> {
+    let Some(query) = fts_query(query) else {
+        return Ok(Vec::new());
+    };
+    let mut stmt = conn.prepare(
+        "SELECT m.id, m.content, m.sender, m.timestamp, m.conversation_id, m.pinned, m.content_file,
+                snippet(messages_fts, 0, '<mark>', '</mark>', '…', 12)
+         FROM messages_fts JOIN messages m ON m.rowid = messages_fts.rowid
+         WHERE messages_fts MATCH ?1 AND m.deleted_at IS NULL
+         ORDER BY bm25(messages_fts)
+         LIMIT ?2",
+    )?;
+    let rows = stmt.query_map(params![query, limit], |row| {
+        let mut message = message_from_row(row)?;
+        message.snippet = row.get(7)?;
+        Ok(message)
+    })?;
+    rows.collect()
+}
+
+/// Quotes each whitespace-separated term so user input can't trip FTS5
+/// query syntax; the terms are implicitly ANDed. `None` for a blank query.
+fn fts_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+/// Sets the system prompt for a conversation; an empty prompt removes it.
+pub fn set_system_prompt(conn: &Connection, conversation_id: &str, prompt: &str) -> rusqlite::Result<()> {
+    if prompt.trim().is_empty() {
+        conn.execute("DELETE FROM system_prompts WHERE conversation_id = ?1", params![conversation_id])?;
+    } else {
+        conn.execute(
+            "INSERT INTO system_prompts (conversation_id, prompt) VALUES (?1, ?2)
+             ON CONFLICT (conversation_id) DO UPDATE SET prompt = excluded.prompt",
+            params![conversation_id, prompt],
+        )?;
+    }
+    Ok(())
+}
+
+pub fn get_system_prompt(conn: &Connection, conversation_id: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT prompt FROM system_prompts WHERE conversation_id = ?1",
+        params![conversation_id],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Per-conversation overrides of the global model and sampling parameters.
+/// Unset fields fall back to the global defaults.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct ConversationSettings {
+    #[serde(default)]
+    pub model_id: Option<String>,
+    #[serde(default)]
+    pub params: GenerationParams,
+}
+
+pub fn set_conversation_settings(
+    conn: &Connection,
+    conversation_id: &str,
+    settings: &ConversationSettings,
+) -> rusqlite::Result<()> {
+    let params_json =
+        serde_json::to_string(&settings.params).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    conn.execute(
+        "INSERT INTO conversation_settings (conversation_id, model_id, params) VALUES (?1, ?2, ?3)
+         ON CONFLICT (conversation_id) DO UPDATE SET model_id = excluded.model_id, params = excluded.params",
+        params![conversation_id, settings.model_id, params_json],
+    )?;
+    Ok(())
+}
+
+/// The conversation's overrides, or empty ones if none were set.
+pub fn get_conversation_settings(conn: &Connection, conversation_id: &str) -> rusqlite::Result<ConversationSettings> {
+    let row: Option<(Option<String>, String)> = conn
+        .query_row(
+            "SELECT model_id, params FROM conversation_settings WHERE conversation_id = ?1",
+            params![conversation_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let Some((model_id, params_json)) = row else {
+        return Ok(ConversationSettings::default());
+    };
+    let params = serde_json::from_str(&params_json)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, Box::new(e)))?;
+    Ok(ConversationSettings { model_id, params })
+}
+
+/// Saves the unsent text of a conversation's input; empty text removes it.
+pub fn save_draft(conn: &Connection, conversation_id: &str, text: &str, updated_at: &str) -> rusqlite::Result<()> {
+    if text.is_empty() {
+        return clear_draft(conn, conversation_id);
+    }
+    conn.execute(
+        "INSERT INTO drafts (conversation_id, text, updated_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT (conversation_id) DO UPDATE SET text = excluded.text, updated_at = excluded.updated_at",
+        params![conversation_id, text, updated_at],
+    )?;
+    Ok(())
+}
+
+pub fn load_draft(conn: &Connection, conversation_id: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row("SELECT text FROM drafts WHERE conversation_id = ?1", params![conversation_id], |row| row.get(0))
+        .optional()
+}
+
+pub fn clear_draft(conn: &Connection, conversation_id: &str) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM drafts WHERE conversation_id = ?1", params![conversation_id])?;
+    Ok(())
+}
+
+/// A stored recap from `summarize_conversation`, valid while the
+/// conversation's last message is still the one at `up_to`.
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub up_to: String,
+    pub text: String,
+}
+
+pub fn get_summary(conn: &Connection, conversation_id: &str) -> rusqlite::Result<Option<Summary>> {
+    conn.query_row(
+        "SELECT up_to, summary FROM conversation_summaries WHERE conversation_id = ?1",
+        params![conversation_id],
+        |row| Ok(Summary { up_to: row.get(0)?, text: row.get(1)? }),
+    )
+    .optional()
+}
+
+pub fn save_summary(conn: &Connection, conversation_id: &str, summary: &Summary) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO conversation_summaries (conversation_id, up_to, summary) VALUES (?1, ?2, ?3)
+         ON CONFLICT (conversation_id) DO UPDATE SET up_to = excluded.up_to, summary = excluded.summary",
+        params![conversation_id, summary.up_to, summary.text],
+    )?;
+    Ok(())
+}
+
+/// A prompt waiting in the outbox to be sent. `failed` entries ran out of
+/// attempts and are no longer retried.
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub conversation_id: String,
+    pub message: String,
+    pub created_at: String,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub failed: bool,
+}
+
+pub fn enqueue_outbox(conn: &Connection, conversation_id: &str, message: &str, created_at: &str) -> rusqlite::Result<i64> {
+    conn.execute(
+        "INSERT INTO outbox (conversation_id, message, created_at) VALUES (?1, ?2, ?3)",
+        params![conversation_id, message, created_at],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Entries oldest first; with `pending_only`, just those still to be retried.
+pub fn load_outbox(conn: &Connection, pending_only: bool) -> rusqlite::Result<Vec<OutboxEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, conversation_id, message, created_at, attempts, last_error, failed FROM outbox
+         WHERE NOT (?1 AND failed) ORDER BY id",
+    )?;
+    let rows = stmt.query_map(params![pending_only], |row| {
+        Ok(OutboxEntry {
+            id: row.get(0)?,
+            conversation_id: row.get(1)?,
+            message: row.get(2)?,
+            created_at: row.get(3)?,
+            attempts: row.get(4)?,
+            last_error: row.get(5)?,
+            failed: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn remove_outbox(conn: &Connection, id: i64) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM outbox WHERE id = ?1", params![id])?;
+    Ok(())
+}
+
+/// Counts a failed send, marking the entry failed once it has had
+/// `max_attempts`. Returns whether it did.
+pub fn record_outbox_failure(conn: &Connection, id: i64, error: &str, max_attempts: u32) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "UPDATE outbox SET attempts = attempts + 1, last_error = ?2, failed = attempts + 1 >= ?3
+         WHERE id = ?1 RETURNING failed",
+        params![id, error, max_attempts],
+        |row| row.get(0),
+    )
+}
+
+/// A reusable prompt with `{{placeholder}}`s, see `templates::render`.
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct Template {
+    pub name: String,
+    pub body: String,
+}
+
+/// Saves a template, replacing any with the same name.
+pub fn save_template(conn: &Connection, name: &str, body: &str, updated_at: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO templates (name, body, updated_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT (name) DO UPDATE SET body = excluded.body, updated_at = excluded.updated_at",
+        params![name, body, updated_at],
+    )?;
+    Ok(())
+}
+
+pub fn load_templates(conn: &Connection) -> rusqlite::Result<Vec<Template>> {
+    let mut stmt = conn.prepare("SELECT name, body FROM templates ORDER BY name")?;
+    let rows = stmt.query_map([], |row| Ok(Template { name: row.get(0)?, body: row.get(1)? }))?;
+    rows.collect()
+}
+
+pub fn get_template(conn: &Connection, name: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row("SELECT body FROM templates WHERE name = ?1", params![name], |row| row.get(0))
+        .optional()
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ConversationSummary {
+    pub id: String,
+    pub title: Option<String>,
+    pub updated_at: String,
+    /// The conversation this one was branched from, if any.
+    pub parent_id: Option<String>,
+}
+
+/// All conversations, most recently updated first.
+pub fn load_conversations(conn: &Connection) -> rusqlite::Result<Vec<ConversationSummary>> {
+    let mut stmt = conn.prepare("SELECT id, title, updated_at, parent_id FROM conversations ORDER BY updated_at DESC")?;
+    let rows = stmt.query_map([], |row| {
+        Ok(ConversationSummary {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            updated_at: row.get(2)?,
+            parent_id: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Copies the messages of `from_message_id`'s conversation, up to and
+/// including that message, into a new conversation `branch_id` that records
+/// the original as its parent. The title, system prompt and conversation
+/// settings carry over.
+/// Returns `false` if there is no such message.
+pub fn branch_conversation(conn: &Connection, from_message_id: &str, branch_id: &str) -> rusqlite::Result<bool> {
+    let parent_id: Option<String> = conn
+        .query_row(
+            "SELECT conversation_id FROM messages WHERE id = ?1 AND deleted_at IS NULL",
+            params![from_message_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let Some(parent_id) = parent_id else {
+        return Ok(false);
+    };
+
+    let tx = conn.unchecked_transaction()?;
+    let mut copied = Vec::new();
+    for message in load_messages(&tx, Some(&parent_id))? {
+        let last = message.id == from_message_id;
+        copied.push(message);
+        if last {
+            break;
+        }
+    }
+    for message in copied {
+        insert_message(
+            &tx,
+            &ChatMessage {
+                id: uuid::Uuid::new_v4().to_string(),
+                conversation_id: branch_id.to_string(),
+                ..message
+            },
+        )?;
+    }
+    tx.execute(
+        "UPDATE conversations SET parent_id = ?2, title = (SELECT title FROM conversations WHERE id = ?2) WHERE id = ?1",
+        params![branch_id, parent_id],
+    )?;
+    tx.execute(
+        "INSERT INTO system_prompts (conversation_id, prompt)
+         SELECT ?1, prompt FROM system_prompts WHERE conversation_id = ?2",
+        params![branch_id, parent_id],
+    )?;
+    tx.execute(
+        "INSERT INTO conversation_settings (conversation_id, model_id, params)
+         SELECT ?1, model_id, params FROM conversation_settings WHERE conversation_id = ?2",
+        params![branch_id, parent_id],
+    )?;
+    tx.commit()?;
+    Ok(true)
+}
+
+pub fn conversation_has_title(conn: &Connection, conversation_id: &str) -> rusqlite::Result<bool> {
+    let title: Option<Option<String>> = conn
+        .query_row(
+            "SELECT title FROM conversations WHERE id = ?1",
+            params![conversation_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(matches!(title, Some(Some(_))))
+}
+
+pub fn set_conversation_title(conn: &Connection, conversation_id: &str, title: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE conversations SET title = ?2 WHERE id = ?1",
+        params![conversation_id, title],
+    )?;
+    Ok(())
+}
+
+pub fn record_usage(
+    conn: &Connection,
+    timestamp: &str,
+    model: &str,
+    prompt_tokens: usize,
+    completion_tokens: usize,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO usage (timestamp, model, prompt_tokens, completion_tokens) VALUES (?1, ?2, ?3, ?4)",
+        params![timestamp, model, prompt_tokens as i64, completion_tokens as i64],
+    )?;
+    Ok(())
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct UsageStats {
+    pub generations: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+}
+
+pub fn usage_stats(conn: &Connection) -> rusqlite::Result<UsageStats> {
+    conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(prompt_tokens), 0), COALESCE(SUM(completion_tokens), 0) FROM usage",
+        [],
+        |row| {
+            let prompt_tokens: u64 = row.get(1)?;
+            let completion_tokens: u64 = row.get(2)?;
+            Ok(UsageStats {
+                generations: row.get(0)?,
+                prompt_tokens,
+                completion_tokens,
+                total_tokens: prompt_tokens + completion_tokens,
+            })
+        },
+    )
+}
+
+/// Converts an ISO 8601 timestamp to epoch millis, the form timestamps are
+/// stored in. `None` if SQLite can't parse it.
+fn iso_to_millis(conn: &Connection, timestamp: &str) -> rusqlite::Result<Option<i64>> {
+    conn.query_row(
+        "SELECT CAST((julianday(?1) - 2440587.5) * 86400000 AS INTEGER)",
+        params![timestamp],
+        |row| row.get(0),
+    )
+}
+
+/// One request sent to a provider. The prompt is kept only as a hash.
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct AuditEntry {
+    pub timestamp: String,
+    pub model: String,
+    /// Hex SHA-256 of the prompt, so repeated prompts can be spotted.
+    pub prompt_hash: String,
+    pub prompt_len: usize,
+    pub response_len: usize,
+    pub latency_ms: u64,
+    /// `ok`, `error` or `cancelled`.
+    pub outcome: String,
+}
+
+pub fn record_audit(conn: &Connection, entry: &AuditEntry) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO audit_log (timestamp, model, prompt_hash, prompt_len, response_len, latency_ms, outcome)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            entry.timestamp,
+            entry.model,
+            entry.prompt_hash,
+            entry.prompt_len as i64,
+            entry.response_len as i64,
+            entry.latency_ms as i64,
+            entry.outcome
+        ],
+    )?;
+    Ok(())
+}
+
+/// Audit entries between two ISO 8601 timestamps (either bound optional),
+/// oldest first. `None` if SQLite can't parse a bound.
+pub fn audit_log(conn: &Connection, since: Option<&str>, until: Option<&str>) -> rusqlite::Result<Option<Vec<AuditEntry>>> {
+    let mut bounds = [i64::MIN, i64::MAX];
+    for (bound, timestamp) in bounds.iter_mut().zip([since, until]) {
+        if let Some(timestamp) = timestamp {
+            let Some(ms) = iso_to_millis(conn, timestamp)? else {
+                return Ok(None);
+            };
+            *bound = ms;
+        }
+    }
+    let mut stmt = conn.prepare(
+        "SELECT timestamp, model, prompt_hash, prompt_len, response_len, latency_ms, outcome FROM audit_log
+         WHERE CAST(timestamp AS INTEGER) BETWEEN ?1 AND ?2 ORDER BY CAST(timestamp AS INTEGER), id",
+    )?;
+    let rows = stmt.query_map(params![bounds[0], bounds[1]], |row| {
+        Ok(AuditEntry {
+            timestamp: row.get(0)?,
+            model: row.get(1)?,
+            prompt_hash: row.get(2)?,
+            prompt_len: row.get::<_, i64>(3)? as usize,
+            response_len: row.get::<_, i64>(4)? as usize,
+            latency_ms: row.get::<_, i64>(5)? as u64,
+            outcome: row.get(6)?,
+        })
+    })?;
+    rows.collect::<rusqlite::Result<_>>().map(Some)
+}
+
+pub fn record_latency(conn: &Connection, timestamp: &str, model: &str, tokens: usize, latency_ms: u64) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO metrics (timestamp, model, tokens, latency_ms) VALUES (?1, ?2, ?3, ?4)",
+        params![timestamp, model, tokens as i64, latency_ms as i64],
+    )?;
+    Ok(())
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct LatencyPoint {
+    pub timestamp: String,
+    pub model: String,
+    pub tokens: u64,
+    pub latency_ms: u64,
+}
+
+/// Generation latencies recorded after `since`, an ISO 8601 timestamp, oldest
+/// first. `None` if SQLite can't parse `since`.
+pub fn latency_history(conn: &Connection, since: &str) -> rusqlite::Result<Option<Vec<LatencyPoint>>> {
+    let Some(since_ms) = iso_to_millis(conn, since)? else {
+        return Ok(None);
+    };
+    let mut stmt = conn.prepare(
+        "SELECT timestamp, model, tokens, latency_ms FROM metrics
+         WHERE CAST(timestamp AS INTEGER) > ?1 ORDER BY CAST(timestamp AS INTEGER)",
+    )?;
+    let rows = stmt.query_map(params![since_ms], |row| {
+        Ok(LatencyPoint {
+            timestamp: row.get(0)?,
+            model: row.get(1)?,
+            tokens: row.get(2)?,
+            latency_ms: row.get(3)?,
+        })
+    })?;
+    rows.collect::<rusqlite::Result<_>>().map(Some)
+}
+
+/// Messages of a conversation that have no embedding from `model` yet.
+pub fn unembedded_messages(conn: &Connection, conversation_id: &str, model: &str) -> rusqlite::Result<Vec<ChatMessage>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM messages
+         WHERE conversation_id = ?1 AND deleted_at IS NULL
+           AND id NOT IN (SELECT message_id FROM message_embeddings WHERE model = ?2)
+         ORDER BY timestamp, rowid",
+        MESSAGE_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![conversation_id, model], message_from_row)?;
+    rows.collect()
+}
+
+/// Stores `vector` for a message, replacing any earlier embedding of it.
+pub fn save_embedding(conn: &Connection, message_id: &str, model: &str, vector: &[f32]) -> rusqlite::Result<()> {
+    let blob: Vec<u8> = vector.iter().flat_map(|x| x.to_le_bytes()).collect();
+    conn.execute(
+        "INSERT INTO message_embeddings (message_id, model, vector) VALUES (?1, ?2, ?3)
+         ON CONFLICT (message_id) DO UPDATE SET model = excluded.model, vector = excluded.vector",
+        params![message_id, model, blob],
+    )?;
+    Ok(())
+}
+
+/// Every message embedded with `model`, together with its vector.
+pub fn load_embeddings(conn: &Connection, model: &str) -> rusqlite::Result<Vec<(ChatMessage, Vec<f32>)>> {
+    let mut stmt = conn.prepare(
+        "SELECT m.id, m.content, m.sender, m.timestamp, m.conversation_id, m.pinned, m.content_file, e.vector
+         FROM message_embeddings e JOIN messages m ON m.id = e.message_id
+         WHERE e.model = ?1 AND m.deleted_at IS NULL",
+    )?;
+    let rows = stmt.query_map(params![model], |row| {
+        let blob: Vec<u8> = row.get(7)?;
+        let vector = blob
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        Ok((message_from_row(row)?, vector))
+    })?;
+    rows.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Role;
+
+    #[test]
+    fn history_works_without_a_token() {
+        std::env::remove_var("HF_TOKEN");
+        let conn = open(Path::new(":memory:")).unwrap();
+        insert_message(&conn, &ChatMessage::new("chat", "hello there".into(), Role::User)).unwrap();
+        insert_message(&conn, &ChatMessage::new("chat", "hi".into(), Role::Assistant)).unwrap();
+
+        assert_eq!(load_messages(&conn, Some("chat")).unwrap().len(), 2);
+        assert_eq!(load_recent_messages(&conn, "chat", 1).unwrap().len(), 1);
+        assert_eq!(search_messages(&conn, "hello", 10).unwrap().len(), 1);
+        assert_eq!(load_conversations(&conn).unwrap().len(), 1);
+    }
+
+    fn count(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn delete_conversation_leaves_no_orphans() {
+        let conn = open(Path::new(":memory:")).unwrap();
+        let question = ChatMessage::new("doomed", "what is rust".into(), Role::User);
+        let answer = ChatMessage::new("doomed", "a language".into(), Role::Assistant);
+        insert_message(&conn, &question).unwrap();
+        insert_message(&conn, &answer).unwrap();
+        insert_message_meta(&conn, &answer.id, &MessageMeta { model: Some("gpt2".into()), ..Default::default() }).unwrap();
+        save_embedding(&conn, &question.id, "minilm", &[0.1, 0.2]).unwrap();
+        let gone = ChatMessage::new("doomed", "rust again".into(), Role::User);
+        insert_message(&conn, &gone).unwrap();
+        soft_delete_message(&conn, &gone.id, "1").unwrap();
+        set_system_prompt(&conn, "doomed", "be brief").unwrap();
+        set_conversation_settings(&conn, "doomed", &ConversationSettings::default()).unwrap();
+        save_draft(&conn, "doomed", "half a thought", "1").unwrap();
+        save_summary(&conn, "doomed", &Summary { up_to: answer.timestamp.clone(), text: "rust".into() }).unwrap();
+        enqueue_outbox(&conn, "doomed", "later", "1").unwrap();
+        assert!(branch_conversation(&conn, &question.id, "branch").unwrap());
+        insert_message(&conn, &ChatMessage::new("kept", "rust too".into(), Role::User)).unwrap();
+
+        assert_eq!(delete_conversation(&conn, "doomed").unwrap(), 3);
+
+        for table in ["messages", "system_prompts", "conversation_settings", "drafts", "conversation_summaries", "outbox"] {
+            let left = count(&conn, &format!("SELECT COUNT(*) FROM {} WHERE conversation_id = 'doomed'", table));
+            assert_eq!(left, 0, "rows left in {}", table);
+        }
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM conversations WHERE id = 'doomed' OR parent_id = 'doomed'"), 0);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM message_embeddings WHERE message_id NOT IN (SELECT id FROM messages)"), 0);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM message_meta WHERE message_id NOT IN (SELECT id FROM messages)"), 0);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM messages_fts"), count(&conn, "SELECT COUNT(*) FROM messages WHERE deleted_at IS NULL"));
+        // The branch and the unrelated conversation are untouched.
+        assert_eq!(load_messages(&conn, Some("branch")).unwrap().len(), 1);
+        assert_eq!(search_messages(&conn, "rust", 10).unwrap().len(), 2);
+    }
+}
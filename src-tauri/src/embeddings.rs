@@ -0,0 +1,114 @@
+use tauri::Manager;
+
+use crate::config::ConfigStore;
+use crate::credentials;
+use crate::db;
+use crate::error::AppError;
+use crate::http::HttpClient;
+use crate::providers::HuggingFace;
+use crate::rate_limit::RateLimiter;
+use crate::retry;
+use crate::ChatMessage;
+
+/// Messages are embedded this many at a time when indexing.
+const INDEX_BATCH_SIZE: usize = 32;
+
+/// Embeds `texts` with the Hugging Face feature-extraction model `model`.
+pub async fn embed(app: &tauri::AppHandle, texts: &[String], model: &str) -> Result<Vec<Vec<f32>>, AppError> {
+    if let Some(i) = texts.iter().position(|t| t.trim().is_empty()) {
+        return Err(AppError::InvalidParams(format!("text {} is empty", i)));
+    }
+    if texts.is_empty() {
+        return Ok(Vec::new());
+    }
+    let http = app.state::<HttpClient>();
+    let provider = HuggingFace::new(http.get()?, model, credentials::require_token()?);
+    let max_retries = app.state::<ConfigStore>().get()?.max_retries;
+    app.state::<RateLimiter>().acquire().await?;
+    retry::with_backoff(max_retries, || provider.embed(texts))
+        .await
+        .map_err(|e| http.explain(e.into()))
+}
+
+/// One embedding vector per input text, in input order.
+#[tauri::command]
+#[tracing::instrument(skip(app, texts), fields(texts = texts.len()), err)]
+pub async fn generate_embeddings(app: tauri::AppHandle, texts: Vec<String>, model: String) -> Result<Vec<Vec<f32>>, AppError> {
+    embed(&app, &texts, &model).await
+}
+
+/// Embeds every message of the conversation that isn't indexed yet with the
+/// configured embedding model, and returns how many were added.
+#[tauri::command]
+#[tracing::instrument(skip(app), err)]
+pub async fn index_conversation(app: tauri::AppHandle, conversation_id: String) -> Result<usize, AppError> {
+    let model = app.state::<ConfigStore>().get()?.embedding_model;
+    let pending = {
+        let db = app.state::<db::Db>();
+        let conn = db.0.lock()?;
+        db::unembedded_messages(&conn, &conversation_id, &model)?
+    };
+    let pending: Vec<ChatMessage> = pending.into_iter().filter(|m| !m.content.trim().is_empty()).collect();
+
+    for batch in pending.chunks(INDEX_BATCH_SIZE) {
+        let texts: Vec<String> = batch.iter().map(|m| m.content.clone()).collect();
+        let vectors = embed(&app, &texts, &model).await?;
+        let db = app.state::<db::Db>();
+        let conn = db.0.lock()?;
+        for (message, vector) in batch.iter().zip(&vectors) {
+            db::save_embedding(&conn, &message.id, &model, vector)?;
+        }
+    }
+    Ok(pending.len())
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct ScoredMessage {
+    #[serde(flatten)]
+    message: ChatMessage,
+    /// Cosine similarity to the query, from -1 to 1.
+    score: f32,
+}
+
+/// The `top_k` indexed messages closest in meaning to `query`, best first.
+/// Returns nothing, without calling the model, if no messages are indexed.
+#[tauri::command]
+#[tracing::instrument(skip(app, query), err)]
+pub async fn semantic_search(app: tauri::AppHandle, query: String, top_k: usize) -> Result<Vec<ScoredMessage>, AppError> {
+    let model = app.state::<ConfigStore>().get()?.embedding_model;
+    let indexed = {
+        let db = app.state::<db::Db>();
+        let conn = db.0.lock()?;
+        db::load_embeddings(&conn, &model)?
+    };
+    if indexed.is_empty() || top_k == 0 {
+        return Ok(Vec::new());
+    }
+    let query_vector = embed(&app, &[query], &model).await?.remove(0);
+
+    let mut scored: Vec<ScoredMessage> = indexed
+        .into_iter()
+        .map(|(message, vector)| ScoredMessage {
+            score: cosine_similarity(&query_vector, &vector),
+            message,
+        })
+        .collect();
+    scored.sort_by(|a, b| b.score.total_cmp(&a.score));
+    scored.truncate(top_k);
+    Ok(scored)
+}
+
+/// 0 for vectors of different lengths or zero magnitude.
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
@@ -0,0 +1,143 @@
+use std::sync::PoisonError;
+
+use serde::ser::SerializeStruct;
+
+use crate::providers::{ProviderError, ValidationError};
+
+/// Error type returned by every command. Serializes as `{ kind, message }` so
+/// the frontend can branch on `kind` (e.g. retry on `network`, prompt for
+/// configuration on `missing_token`) without parsing message text.
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("no Hugging Face API token set")]
+    MissingToken,
+    #[error("network error: {0}")]
+    Network(reqwest::Error),
+    #[error("request timed out")]
+    Timeout,
+    #[error("cannot reach {0}, check your internet connection")]
+    Offline(String),
+    #[error("could not reach proxy {0}")]
+    ProxyUnreachable(String),
+    #[error("API error {code}: {body}{}", request_id_suffix(.request_id))]
+    ApiStatus {
+        code: u16,
+        body: String,
+        request_id: Option<String>,
+    },
+    #[error("failed to parse response: {0}")]
+    Parse(String),
+    #[error("model error: {0}")]
+    Model(String),
+    #[error("invalid parameters: {0}")]
+    InvalidParams(String),
+    /// Same kind as `InvalidParams`, but also names the offending field.
+    #[error("invalid parameters: {0}")]
+    Validation(#[from] ValidationError),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("internal state lock poisoned")]
+    LockPoisoned,
+    #[error("cancelled")]
+    Cancelled,
+    #[error("credential store error: {0}")]
+    Credentials(String),
+    #[error("global shortcut error: {0}")]
+    Shortcut(String),
+    #[error("clipboard error: {0}")]
+    Clipboard(String),
+    #[error("prompt blocked by pattern {pattern:?}")]
+    Blocked { pattern: String },
+    #[error("rate limit reached, retry in {retry_after_secs}s")]
+    RateLimited { retry_after_secs: u64 },
+    #[error("busy: {0}")]
+    Busy(String),
+    /// A bug on our side, such as a background task that panicked.
+    #[error("internal error: {0}")]
+    Internal(String),
+    #[error("tauri error: {0}")]
+    Tauri(#[from] tauri::Error),
+}
+
+impl AppError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppError::MissingToken => "missing_token",
+            AppError::Network(_) => "network",
+            AppError::Timeout => "timeout",
+            AppError::Offline(_) => "offline",
+            AppError::ProxyUnreachable(_) => "proxy_unreachable",
+            AppError::ApiStatus { .. } => "api_status",
+            AppError::Parse(_) => "parse",
+            AppError::Model(_) => "model",
+            AppError::InvalidParams(_) | AppError::Validation(_) => "invalid_params",
+            AppError::Io(_) => "io",
+            AppError::Database(_) => "database",
+            AppError::LockPoisoned => "lock_poisoned",
+            AppError::Cancelled => "cancelled",
+            AppError::Credentials(_) => "credentials",
+            AppError::Shortcut(_) => "shortcut",
+            AppError::Clipboard(_) => "clipboard",
+            AppError::Blocked { .. } => "blocked",
+            AppError::RateLimited { .. } => "rate_limited",
+            AppError::Busy(_) => "busy",
+            AppError::Internal(_) => "internal",
+            AppError::Tauri(_) => "tauri",
+        }
+    }
+}
+
+fn request_id_suffix(request_id: &Option<String>) -> String {
+    request_id
+        .as_ref()
+        .map(|id| format!(" (request id {})", id))
+        .unwrap_or_default()
+}
+
+/// API errors also carry `request_id` when the server sent one, and
+/// validation errors the `field` at fault.
+impl serde::Serialize for AppError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let extra = match self {
+            AppError::ApiStatus { request_id: Some(request_id), .. } => Some(("request_id", request_id.as_str())),
+            AppError::Validation(err) => Some(("field", err.field)),
+            _ => None,
+        };
+        let mut state = serializer.serialize_struct("AppError", if extra.is_some() { 3 } else { 2 })?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        if let Some((key, value)) = extra {
+            state.serialize_field(key, value)?;
+        }
+        state.end()
+    }
+}
+
+impl From<ProviderError> for AppError {
+    fn from(err: ProviderError) -> Self {
+        match err {
+            ProviderError::Network(e) => e.into(),
+            ProviderError::Api { status, body, request_id, .. } => AppError::ApiStatus { code: status, body, request_id },
+            ProviderError::InvalidResponse(msg) => AppError::Parse(msg),
+            ProviderError::Model(msg) => AppError::Model(msg),
+        }
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            AppError::Timeout
+        } else {
+            AppError::Network(err)
+        }
+    }
+}
+
+impl<T> From<PoisonError<T>> for AppError {
+    fn from(_: PoisonError<T>) -> Self {
+        AppError::LockPoisoned
+    }
+}
@@ -0,0 +1,185 @@
+use crate::db::{AuditEntry, ConversationSummary};
+use crate::error::AppError;
+use crate::timestamps;
+use crate::ChatMessage;
+
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+}
+
+pub fn render(messages: &[ChatMessage], format: ExportFormat) -> Result<String, AppError> {
+    match format {
+        ExportFormat::Markdown => Ok(to_markdown(messages)),
+        ExportFormat::Json => serde_json::to_string_pretty(messages).map_err(|e| AppError::Parse(e.to_string())),
+    }
+}
+
+/// Reads messages in the form `ExportFormat::Json` writes them. Each record
+/// must be a valid message with a known role and a timestamp in RFC 3339 or
+/// epoch milliseconds, which is stored as RFC 3339 in UTC; the error names
+/// the first one that isn't.
+pub fn parse_messages(json: &str) -> Result<Vec<ChatMessage>, AppError> {
+    let records: Vec<serde_json::Value> =
+        serde_json::from_str(json).map_err(|e| AppError::InvalidParams(format!("expected a JSON array of messages: {}", e)))?;
+    parse_records(records).map_err(AppError::InvalidParams)
+}
+
+fn parse_records(records: Vec<serde_json::Value>) -> Result<Vec<ChatMessage>, String> {
+    records
+        .into_iter()
+        .enumerate()
+        .map(|(i, record)| {
+            let invalid = |reason: String| format!("message {}: {}", i, reason);
+            let mut message: ChatMessage = serde_json::from_value(record).map_err(|e| invalid(e.to_string()))?;
+            message.timestamp = timestamps::normalize(&message.timestamp)
+                .ok_or_else(|| invalid(format!("invalid timestamp {:?}", message.timestamp)))?;
+            Ok(message)
+        })
+        .collect()
+}
+
+/// Builds the `export_all` document one conversation and message at a time.
+/// JSON is `{ "conversations": [{ id, title, updated_at, parent_id,
+/// "messages": [...] }] }`, which `parse_bundle` reads back; Markdown puts
+/// each conversation under a heading, rendered as `render` would.
+pub struct BundleWriter {
+    format: ExportFormat,
+    out: String,
+    conversations: usize,
+    messages: usize,
+}
+
+impl BundleWriter {
+    pub fn new(format: ExportFormat) -> Self {
+        let out = match format {
+            ExportFormat::Json => String::from("{\"conversations\":["),
+            ExportFormat::Markdown => String::new(),
+        };
+        BundleWriter { format, out, conversations: 0, messages: 0 }
+    }
+
+    pub fn start_conversation(&mut self, conversation: &ConversationSummary) -> Result<(), AppError> {
+        match self.format {
+            ExportFormat::Json => {
+                if self.conversations > 0 {
+                    self.out.push_str("]},");
+                }
+                let header = serde_json::to_string(conversation).map_err(|e| AppError::Parse(e.to_string()))?;
+                // Reopen the header object to append its messages.
+                self.out.push_str(header.strip_suffix('}').unwrap_or(&header));
+                self.out.push_str(",\"messages\":[");
+            }
+            ExportFormat::Markdown => {
+                if self.conversations > 0 {
+                    self.out.push_str("\n\n");
+                }
+                let title = conversation.title.as_deref().unwrap_or(&conversation.id);
+                self.out.push_str(&format!("# {}\n\n", title));
+            }
+        }
+        self.conversations += 1;
+        self.messages = 0;
+        Ok(())
+    }
+
+    pub fn message(&mut self, message: &ChatMessage) -> Result<(), AppError> {
+        if self.messages > 0 {
+            self.out.push_str(match self.format {
+                ExportFormat::Json => ",",
+                ExportFormat::Markdown => "\n---\n\n",
+            });
+        }
+        match self.format {
+            ExportFormat::Json => {
+                let json = serde_json::to_string(message).map_err(|e| AppError::Parse(e.to_string()))?;
+                self.out.push_str(&json);
+            }
+            ExportFormat::Markdown => self.out.push_str(&markdown_message(message)),
+        }
+        self.messages += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> String {
+        if self.format == ExportFormat::Json {
+            self.out.push_str(if self.conversations > 0 { "]}]}" } else { "]}" });
+        }
+        self.out
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct Bundle {
+    conversations: Vec<BundledConversation>,
+}
+
+#[derive(serde::Deserialize)]
+struct BundledConversation {
+    #[serde(flatten)]
+    summary: ConversationSummary,
+    messages: Vec<serde_json::Value>,
+}
+
+/// Reads a JSON `export_all` bundle, checking each message as
+/// `parse_messages` does.
+pub fn parse_bundle(json: &str) -> Result<Vec<(ConversationSummary, Vec<ChatMessage>)>, AppError> {
+    let bundle: Bundle = serde_json::from_str(json)
+        .map_err(|e| AppError::InvalidParams(format!("expected an export_all JSON bundle: {}", e)))?;
+    bundle
+        .conversations
+        .into_iter()
+        .map(|mut conversation| {
+            // Bundles from before RFC 3339 timestamps carry epoch millis.
+            if let Some(updated_at) = timestamps::normalize(&conversation.summary.updated_at) {
+                conversation.summary.updated_at = updated_at;
+            }
+            let messages = parse_records(conversation.messages)
+                .map_err(|e| AppError::InvalidParams(format!("conversation {}: {}", conversation.summary.id, e)))?;
+            Ok((conversation.summary, messages))
+        })
+        .collect()
+}
+
+/// Renders audit entries as CSV with a header row.
+pub fn audit_csv(entries: &[AuditEntry]) -> String {
+    let mut csv = String::from("timestamp,model,prompt_hash,prompt_len,response_len,latency_ms,outcome\n");
+    for e in entries {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            csv_field(&e.timestamp),
+            csv_field(&e.model),
+            e.prompt_hash,
+            e.prompt_len,
+            e.response_len,
+            e.latency_ms,
+            csv_field(&e.outcome)
+        ));
+    }
+    csv
+}
+
+/// Quotes a field if it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Each message becomes a bold sender header, its content, then an italic
+/// timestamp, with a horizontal rule between messages.
+fn to_markdown(messages: &[ChatMessage]) -> String {
+    messages
+        .iter()
+        .map(markdown_message)
+        .collect::<Vec<_>>()
+        .join("\n---\n\n")
+}
+
+fn markdown_message(m: &ChatMessage) -> String {
+    format!("**{}**\n\n{}\n\n_{}_\n", m.sender.label(), m.content, m.timestamp)
+}
@@ -0,0 +1,1080 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+use tauri::Manager;
+
+use crate::active::ActiveQueries;
+use crate::attachments;
+use crate::blocklist::Blocklist;
+use crate::cache::ResponseCache;
+use crate::cancel::{InFlightRequests, RunningBatches};
+use crate::config::{ConfigStore, ModelState};
+use crate::connectivity::Connectivity;
+use crate::context;
+use crate::conversations;
+use crate::data_dir::DataDir;
+use crate::db;
+use crate::error::AppError;
+use crate::http::HttpClient;
+use crate::models;
+use crate::postprocess;
+use crate::providers::{
+    self, AiProvider, GenerationParams, Interrupted, PromptMessage, ProviderConfig, ProviderKind, ResponseFormat, SseBuffer,
+    StreamLine,
+};
+use crate::rate_limit::RateLimiter;
+use crate::retry;
+use crate::sidecar;
+use crate::smoothing::{self, Pacer, StreamGranularity};
+use crate::tasks::{self, TaskKind};
+use crate::templates;
+use crate::tokens;
+use crate::tools;
+use crate::writer::WriteQueue;
+use crate::{now_timestamp, ChatMessage, MessageMeta, Role};
+
+/// How much `regenerate_response` raises the temperature over the previous
+/// attempt, so a re-roll actually differs.
+const REGENERATE_TEMPERATURE_STEP: f32 = 0.2;
+/// Temperature assumed for the previous attempt when none was given.
+const DEFAULT_TEMPERATURE: f32 = 0.7;
+/// How often `ai-progress` heartbeats are sent while waiting on a reply.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// Tokens kept free for the reply when `max_new_tokens` isn't given.
+const DEFAULT_REPLY_TOKENS: usize = 512;
+/// Appended to the prompt when a JSON reply is requested.
+const JSON_INSTRUCTION: &str = "\n\nRespond with a single valid JSON value and nothing else.";
+/// Prefixes the reply sent back to the model when it wasn't valid JSON.
+const JSON_REPAIR_INSTRUCTION: &str =
+    "The following was meant to be valid JSON but does not parse. Reply with only the corrected JSON.\n\n";
+
+/// Sent as the last user turn of `summarize_conversation`.
+const SUMMARY_INSTRUCTION: &str = "Summarize the conversation so far in a few sentences: what was asked and what was \
+     concluded. Reply with the summary only.";
+
+/// Tool calls `generate_with_tools` runs before asking for a final answer.
+const MAX_TOOL_CALLS: usize = 4;
+/// Sent once the model has used up `MAX_TOOL_CALLS`.
+const TOOLS_EXHAUSTED: &str = "No more tool calls are allowed. Give your final answer with what you have.";
+
+/// Payload of the `ai-progress` event.
+#[derive(serde::Serialize, Clone)]
+struct Progress<'a> {
+    request_id: Option<&'a str>,
+    status: String,
+}
+
+/// One exchange to send to the model: the user's message as stored, the chat
+/// built around it (also flattened into `prompt` for caching and token
+/// counts), and the model and sampling parameters with the conversation's
+/// overrides applied.
+struct Turn {
+    conversation_id: String,
+    user_message: String,
+    messages: Vec<PromptMessage>,
+    prompt: String,
+    model: String,
+    params: GenerationParams,
+    /// Older messages were left out to fit the context window.
+    context_truncated: bool,
+}
+
+impl Turn {
+    /// Trims `messages` to fit `context_window` tokens (0 for no limit) with
+    /// room left for the reply.
+    fn new(
+        conversation_id: String,
+        user_message: String,
+        mut messages: Vec<PromptMessage>,
+        model: String,
+        params: GenerationParams,
+        context_window: usize,
+    ) -> Self {
+        let mut context_truncated = false;
+        if context_window > 0 {
+            let budget = prompt_budget(context_window, &params);
+            context_truncated = context::fit_to_budget(&mut messages, budget, |text| tokens::count_tokens(text, &model));
+            if context_truncated {
+                tracing::info!(kept = messages.len(), budget, "dropped old messages to fit the context window");
+            }
+        }
+        Turn {
+            conversation_id,
+            user_message,
+            prompt: context::format_prompt(&messages),
+            messages,
+            model,
+            params,
+            context_truncated,
+        }
+    }
+}
+
+pub fn current_model(model: &ModelState) -> Result<String, AppError> {
+    Ok(model.0.lock()?.clone())
+}
+
+/// The model and parameters to use in `conversation_id`: `params` given with
+/// the request win, then the conversation's settings, then the globals.
+fn resolve_settings(
+    app: &tauri::AppHandle,
+    conversation_id: &str,
+    params: Option<GenerationParams>,
+) -> Result<(String, GenerationParams), AppError> {
+    let settings = {
+        let db = app.state::<db::Db>();
+        let conn = db.0.lock()?;
+        db::get_conversation_settings(&conn, conversation_id)?
+    };
+    let model = match settings.model_id {
+        Some(model) => model,
+        None => current_model(&app.state::<ModelState>())?,
+    };
+    Ok((model, params.unwrap_or_default().or(settings.params)))
+}
+
+/// Loads the conversation's recent turns (capped by `max_context_messages`)
+/// and combines them with the new message into the chat to send.
+fn conversation_messages(
+    app: &tauri::AppHandle,
+    conversation_id: &str,
+    message: &str,
+    max_context_messages: usize,
+) -> Result<Vec<PromptMessage>, AppError> {
+    let db = app.state::<db::Db>();
+    let conn = db.0.lock()?;
+    let system_prompt = db::get_system_prompt(&conn, conversation_id)?;
+    let mut history = db::load_recent_messages(&conn, conversation_id, max_context_messages)?;
+    sidecar::rehydrate_all(&app.state::<DataDir>().0, &mut history);
+    Ok(context::build_messages(system_prompt.as_deref(), &history, message))
+}
+
+fn needs_title(db: &db::Db, conversation_id: &str) -> Result<bool, AppError> {
+    let conn = db.0.lock()?;
+    Ok(!db::conversation_has_title(&conn, conversation_id)?)
+}
+
+/// Queues a usage row and returns the prompt and completion token counts.
+fn record_usage(writes: &WriteQueue, model: &str, prompt: &str, completion: &str) -> Result<(usize, usize), AppError> {
+    let timestamp = now_timestamp();
+    let model = model.to_string();
+    let prompt_tokens = tokens::count_tokens(prompt, &model);
+    let completion_tokens = tokens::count_tokens(completion, &model);
+    writes.enqueue(move |conn| db::record_usage(conn, &timestamp, &model, prompt_tokens, completion_tokens))?;
+    Ok((prompt_tokens, completion_tokens))
+}
+
+fn record_latency(writes: &WriteQueue, model: &str, reply: &str, started: Instant) -> Result<(), AppError> {
+    let timestamp = now_timestamp();
+    let model = model.to_string();
+    let tokens = tokens::count_tokens(reply, &model);
+    let latency_ms = started.elapsed().as_millis() as u64;
+    writes.enqueue(move |conn| db::record_latency(conn, &timestamp, &model, tokens, latency_ms))
+}
+
+/// Logs a request to the audit table. Only a hash of the prompt is kept.
+fn record_audit(writes: &WriteQueue, model: &str, prompt: &str, reply: &str, started: Instant, outcome: &str) -> Result<(), AppError> {
+    let entry = db::AuditEntry {
+        timestamp: now_timestamp(),
+        model: model.to_string(),
+        prompt_hash: format!("{:x}", Sha256::digest(prompt.as_bytes())),
+        prompt_len: prompt.chars().count(),
+        response_len: reply.chars().count(),
+        latency_ms: started.elapsed().as_millis() as u64,
+        outcome: outcome.to_string(),
+    };
+    writes.enqueue(move |conn| db::record_audit(conn, &entry))
+}
+
+/// The form of `message` to store, with oversized content moved out to a
+/// sidecar file per `max_stored_message_chars`.
+fn stored_form(app: &tauri::AppHandle, message: &ChatMessage) -> Result<ChatMessage, AppError> {
+    let max_chars = app.state::<ConfigStore>().get()?.max_stored_message_chars;
+    sidecar::offload(&app.state::<DataDir>().0, message, max_chars)
+}
+
+fn save_message(app: &tauri::AppHandle, message: &ChatMessage) -> Result<(), AppError> {
+    let stored = stored_form(app, message)?;
+    let db = app.state::<db::Db>();
+    let conn = db.0.lock()?;
+    db::insert_message(&conn, &stored)?;
+    Ok(())
+}
+
+/// Tokens the prompt may use out of `context_window`, leaving room for the
+/// reply.
+fn prompt_budget(context_window: usize, params: &GenerationParams) -> usize {
+    let reply_tokens = params.max_new_tokens.map_or(DEFAULT_REPLY_TOKENS, |n| n as usize);
+    context_window.saturating_sub(reply_tokens)
+}
+
+#[derive(serde::Serialize)]
+pub struct ContextFit {
+    /// Tokens in the prompt as it would be sent, before any truncation.
+    token_count: usize,
+    /// Tokens available to the prompt: the context window less room for the
+    /// reply. 0 when no context window is configured.
+    limit: usize,
+    fits: bool,
+    /// Prior messages that would be left out to make it fit.
+    dropped_turns: usize,
+}
+
+/// Checks, without sending anything, whether `new_message` and the
+/// conversation so far fit the context window, counting tokens as `model`
+/// would.
+#[tauri::command]
+#[tracing::instrument(skip(app, new_message), err)]
+pub fn check_context_fit(
+    app: tauri::AppHandle,
+    conversation_id: String,
+    new_message: String,
+    model: String,
+) -> Result<ContextFit, AppError> {
+    let config = app.state::<ConfigStore>().get()?;
+    let (_, params) = resolve_settings(&app, &conversation_id, None)?;
+    let messages = conversation_messages(&app, &conversation_id, &new_message, config.max_context_messages)?;
+    let count = |text: &str| tokens::count_tokens(text, &model);
+    let token_count = count(&context::format_prompt(&messages));
+    if config.context_window == 0 {
+        return Ok(ContextFit { token_count, limit: 0, fits: true, dropped_turns: 0 });
+    }
+    let limit = prompt_budget(config.context_window, &params);
+    let mut trimmed = messages.clone();
+    let truncated = context::fit_to_budget(&mut trimmed, limit, count);
+    Ok(ContextFit {
+        token_count,
+        limit,
+        fits: !truncated && token_count <= limit,
+        dropped_turns: messages.len() - trimmed.len(),
+    })
+}
+
+/// Validates `params`, builds the prompt from the conversation so far and
+/// stores the user's message, refusing it if it matches the blocklist.
+/// `context` goes into the prompt ahead of the message, and the JSON
+/// instruction after it, but neither is stored.
+fn start_turn(
+    app: &tauri::AppHandle,
+    conversation_id: String,
+    message: String,
+    context: Option<&str>,
+    params: Option<GenerationParams>,
+) -> Result<Turn, AppError> {
+    let (turn, user_message) = prepare_turn(app, conversation_id, message, context, params)?;
+    save_message(app, &user_message)?;
+    Ok(turn)
+}
+
+/// `start_turn` without storing the message, which is returned instead.
+fn prepare_turn(
+    app: &tauri::AppHandle,
+    conversation_id: String,
+    message: String,
+    context: Option<&str>,
+    params: Option<GenerationParams>,
+) -> Result<(Turn, ChatMessage), AppError> {
+    app.state::<Blocklist>().check(&message)?;
+    let (model, params) = resolve_settings(app, &conversation_id, params)?;
+    params.validate()?;
+    let mut input = format!("{}{}", context.unwrap_or_default(), message);
+    if params.response_format == ResponseFormat::Json {
+        input.push_str(JSON_INSTRUCTION);
+    }
+    let config = app.state::<ConfigStore>().get()?;
+    let messages = conversation_messages(app, &conversation_id, &input, config.max_context_messages)?;
+    let user_message = ChatMessage::new(&conversation_id, message.clone(), Role::User);
+    Ok((Turn::new(conversation_id, message, messages, model, params, config.context_window), user_message))
+}
+
+/// Answers a prompt from the outbox like `generate_ai_response`, except that
+/// the user's message is taken back out of the history if the send fails,
+/// so a retry doesn't leave a duplicate.
+pub async fn send_queued(window: &tauri::Window, conversation_id: String, message: String) -> Result<String, AppError> {
+    let app = window.app_handle();
+    let (turn, user_message) = prepare_turn(&app, conversation_id, message, None, None)?;
+    save_message(&app, &user_message)?;
+    let result = complete(window, turn, None, None).await;
+    if result.is_err() {
+        let db = app.state::<db::Db>();
+        let conn = db.0.lock()?;
+        db::delete_message(&conn, &user_message.id)?;
+    }
+    result
+}
+
+/// Stores the model's reply with how it was generated and its usage, then
+/// titles the conversation if this was its first exchange.
+fn finish_turn(
+    app: &tauri::AppHandle,
+    provider: Box<dyn AiProvider>,
+    turn: Turn,
+    reply: &str,
+    started: Instant,
+) -> Result<(), AppError> {
+    let (prompt_tokens, completion_tokens) =
+        record_usage(&app.state::<WriteQueue>(), provider.model(), &turn.prompt, reply)?;
+    let meta = MessageMeta {
+        model: Some(provider.model().to_string()),
+        temperature: turn.params.temperature,
+        prompt_tokens: Some(prompt_tokens),
+        completion_tokens: Some(completion_tokens),
+        latency_ms: Some(started.elapsed().as_millis() as u64),
+    };
+    store_reply(app, provider, turn, reply, meta)
+}
+
+/// `finish_turn` for a reply served from the response cache, which used no
+/// tokens, so no usage is recorded.
+fn finish_cached_turn(app: &tauri::AppHandle, provider: Box<dyn AiProvider>, turn: Turn, reply: &str) -> Result<(), AppError> {
+    let meta = MessageMeta {
+        model: Some(provider.model().to_string()),
+        temperature: turn.params.temperature,
+        ..Default::default()
+    };
+    store_reply(app, provider, turn, reply, meta)
+}
+
+fn store_reply(
+    app: &tauri::AppHandle,
+    provider: Box<dyn AiProvider>,
+    turn: Turn,
+    reply: &str,
+    meta: MessageMeta,
+) -> Result<(), AppError> {
+    let db = app.state::<db::Db>();
+    let message = stored_form(app, &ChatMessage::new(&turn.conversation_id, reply.to_string(), Role::Assistant))?;
+    {
+        let conn = db.0.lock()?;
+        db::insert_message(&conn, &message)?;
+        db::insert_message_meta(&conn, &message.id, &meta)?;
+    }
+    if needs_title(&db, &turn.conversation_id)? {
+        conversations::spawn_title_generation(app.clone(), provider, turn.conversation_id, turn.user_message, reply.to_string());
+    }
+    Ok(())
+}
+
+/// Runs a reply through the configured `post_processors`.
+fn post_process(app: &tauri::AppHandle, prompt: &str, reply: String) -> Result<String, AppError> {
+    Ok(postprocess::apply(&app.state::<ConfigStore>().get()?.post_processors, prompt, reply))
+}
+
+fn offline_reply(message: &str) -> String {
+    format!("[offline] You said: {}", message)
+}
+
+/// Answers `turn` with `offline_reply` after the configured delay, without
+/// touching the network. Streams emit the echo word by word over that delay.
+async fn complete_offline(app: &tauri::AppHandle, turn: Turn, window: Option<&tauri::Window>) -> Result<String, AppError> {
+    let delay = Duration::from_millis(app.state::<ConfigStore>().get()?.offline_delay_ms);
+    let reply = post_process(app, &turn.prompt, offline_reply(&turn.user_message))?;
+    match window {
+        Some(window) => {
+            let (tokens, per_token) = smoothing::spread_words(&reply, delay);
+            if tokens.is_empty() {
+                tokio::time::sleep(delay).await;
+            }
+            for token in tokens {
+                tokio::time::sleep(per_token).await;
+                window.emit("ai-token", token)?;
+            }
+        }
+        None => tokio::time::sleep(delay).await,
+    }
+    save_message(app, &ChatMessage::new(&turn.conversation_id, reply.clone(), Role::Assistant))?;
+    if let Some(window) = window {
+        window.emit("ai-done", &reply)?;
+    }
+    Ok(reply)
+}
+
+/// Sends `turn` to the provider, cancellable through `request_id`. A cached
+/// reply to the same prompt is returned without a request; it is stored as
+/// usual but adds no usage. While waiting, `window` gets `ai-progress`
+/// events: retry status such as "model loading, ~20s" and periodic
+/// heartbeats.
+#[tracing::instrument(skip_all, fields(model, prompt_len = turn.prompt.len(), outcome))]
+async fn complete(
+    window: &tauri::Window,
+    turn: Turn,
+    provider: Option<ProviderKind>,
+    request_id: Option<String>,
+) -> Result<String, AppError> {
+    let app = &window.app_handle();
+    if app.state::<ConfigStore>().get()?.offline_mode {
+        return complete_offline(app, turn, None).await;
+    }
+    let provider_config = app.state::<ProviderConfig>();
+    let in_flight = app.state::<InFlightRequests>();
+    let cache = app.state::<ResponseCache>();
+
+    let kind = provider.unwrap_or(provider_config.default_kind);
+    let http = app.state::<HttpClient>();
+    let client = http.get()?;
+    let provider = providers::build(kind, &provider_config, client, &turn.model)?;
+    let span = tracing::Span::current();
+    span.record("model", provider.model());
+    if let Some(reply) = cache.get(provider.model(), &turn.prompt, &turn.params)? {
+        span.record("outcome", "cached");
+        let reply = post_process(app, &turn.prompt, reply)?;
+        finish_cached_turn(app, provider, turn, &reply)?;
+        return Ok(reply);
+    }
+
+    app.state::<Connectivity>().ensure_reachable(&http.get()?, provider.endpoint()).await?;
+    app.state::<RateLimiter>().acquire().await?;
+    let active = app.state::<ActiveQueries>();
+    let _active = active.enter();
+
+    let max_retries = app.state::<ConfigStore>().get()?.max_retries;
+    let started = Instant::now();
+    let registration = request_id.clone().map(|id| in_flight.register(id));
+    let progress = |status: String| {
+        let _ = window.emit("ai-progress", Progress { request_id: request_id.as_deref(), status });
+    };
+    let result = {
+        let generation = retry::with_backoff_notify(
+            max_retries,
+            || provider.chat(&turn.messages, &turn.params),
+            |status| progress(status.to_string()),
+        );
+        let cancelled = async {
+            match &registration {
+                Some(registration) => registration.token().cancelled().await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::pin!(generation, cancelled);
+        let mut heartbeat = tokio::time::interval_at(tokio::time::Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = &mut cancelled => {
+                    span.record("outcome", "cancelled");
+                    record_audit(&app.state::<WriteQueue>(), provider.model(), &turn.prompt, "", started, "cancelled")?;
+                    return Err(AppError::Cancelled);
+                }
+                result = &mut generation => break result,
+                _ = heartbeat.tick() => progress(format!("waiting for reply, {}s elapsed", started.elapsed().as_secs())),
+            }
+        }
+    };
+    let generated_text = match result {
+        Ok(text) => text,
+        Err(e) => {
+            span.record("outcome", "error");
+            record_audit(&app.state::<WriteQueue>(), provider.model(), &turn.prompt, "", started, "error")?;
+            return Err(http.explain(e.into()));
+        }
+    };
+    span.record("outcome", "ok");
+    record_audit(&app.state::<WriteQueue>(), provider.model(), &turn.prompt, &generated_text, started, "ok")?;
+    tracing::info!(reply_len = generated_text.len(), "generation finished");
+    record_latency(&app.state::<WriteQueue>(), provider.model(), &generated_text, started)?;
+    drop(registration);
+
+    cache.put(provider.model(), &turn.prompt, &turn.params, &generated_text)?;
+    let generated_text = post_process(app, &turn.prompt, generated_text)?;
+    finish_turn(app, provider, turn, &generated_text, started)?;
+    Ok(generated_text)
+}
+
+/// A short recap of `conversation_id` from the default provider. The summary
+/// is stored against the timestamp of the last message it covers and reused
+/// until newer messages arrive. An empty conversation gives an empty string
+/// without calling the model; when the chat is too long for the context
+/// window the oldest messages are left out.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn summarize_conversation(app: tauri::AppHandle, conversation_id: String) -> Result<String, AppError> {
+    let (mut history, stored) = {
+        let db = app.state::<db::Db>();
+        let conn = db.0.lock()?;
+        (db::load_messages(&conn, Some(&conversation_id))?, db::get_summary(&conn, &conversation_id)?)
+    };
+    sidecar::rehydrate_all(&app.state::<DataDir>().0, &mut history);
+    let Some(last) = history.last() else {
+        return Ok(String::new());
+    };
+    if let Some(summary) = stored.filter(|summary| summary.up_to == last.timestamp) {
+        return Ok(summary.text);
+    }
+    let config = app.state::<ConfigStore>().get()?;
+    if config.offline_mode {
+        return Ok(format!("[offline] {} messages", history.len()));
+    }
+
+    let defaults = GenerationParams {
+        temperature: Some(0.3),
+        max_new_tokens: Some(200),
+        ..Default::default()
+    };
+    let (model, params) = resolve_settings(&app, &conversation_id, Some(defaults))?;
+    params.validate()?;
+    let mut messages = context::build_messages(None, &history, SUMMARY_INSTRUCTION);
+    if config.context_window > 0 {
+        let budget = prompt_budget(config.context_window, &params);
+        context::fit_to_budget(&mut messages, budget, |text| tokens::count_tokens(text, &model));
+    }
+
+    let provider_config = app.state::<ProviderConfig>();
+    let http = app.state::<HttpClient>();
+    let active = app.state::<ActiveQueries>();
+    let _active = active.enter();
+    let provider = providers::build(provider_config.default_kind, &provider_config, http.get()?, &model)?;
+    app.state::<Connectivity>().ensure_reachable(&http.get()?, provider.endpoint()).await?;
+    app.state::<RateLimiter>().acquire().await?;
+    let reply = retry::with_backoff(config.max_retries, || provider.chat(&messages, &params))
+        .await
+        .map_err(|e| http.explain(e.into()))?;
+    record_usage(&app.state::<WriteQueue>(), provider.model(), &context::format_prompt(&messages), &reply)?;
+
+    let summary = db::Summary {
+        up_to: last.timestamp.clone(),
+        text: reply.trim().to_string(),
+    };
+    let db = app.state::<db::Db>();
+    let conn = db.0.lock()?;
+    db::save_summary(&conn, &conversation_id, &summary)?;
+    Ok(summary.text)
+}
+
+/// Streams `turn` from the default provider, emitting `ai-token` events cut
+/// at `granularity` and `ai-done` with the full text at the end. An
+/// `ai-context-truncated` event comes first if old messages were dropped.
+#[tracing::instrument(skip_all, fields(model, prompt_len = turn.prompt.len()))]
+async fn stream_completion(window: &tauri::Window, turn: Turn, granularity: StreamGranularity) -> Result<String, AppError> {
+    if turn.context_truncated {
+        window.emit("ai-context-truncated", ())?;
+    }
+    if window.state::<ConfigStore>().get()?.offline_mode {
+        return complete_offline(&window.app_handle(), turn, Some(window)).await;
+    }
+    window.state::<RateLimiter>().acquire().await?;
+    let active = window.state::<ActiveQueries>();
+    let _active = active.enter();
+
+    let http = window.state::<HttpClient>();
+    let provider_config = window.state::<ProviderConfig>();
+    let provider = providers::build(provider_config.default_kind, &provider_config, http.get()?, &turn.model)?;
+    tracing::Span::current().record("model", provider.model());
+    window.state::<Connectivity>().ensure_reachable(&http.get()?, provider.endpoint()).await?;
+    let max_retries = window.state::<ConfigStore>().get()?.max_retries;
+    let started = Instant::now();
+    let mut res = retry::with_backoff(max_retries, || provider.stream(&turn.messages, &turn.params))
+        .await
+        .map_err(|e| http.explain(e.into()))?;
+
+    let mut pacer = Pacer::new(granularity);
+    let mut events = SseBuffer::default();
+    let mut full_text = String::new();
+    let mut done = false;
+    while !done {
+        let chunk = tokio::select! {
+            chunk = res.chunk() => chunk?,
+            _ = pacer.tick(), if pacer.has_queued() => {
+                pacer.emit_queued(window)?;
+                continue;
+            }
+        };
+        let Some(chunk) = chunk else {
+            break;
+        };
+        for data in events.push(&chunk) {
+            match provider.parse_stream_data(&data) {
+                StreamLine::Token(token) => {
+                    full_text.push_str(&token);
+                    pacer.push(window, &token)?;
+                }
+                StreamLine::Done => {
+                    done = true;
+                    break;
+                }
+                StreamLine::Skip => {}
+            }
+        }
+    }
+    if !done {
+        match events.finish() {
+            Ok(Some(data)) => {
+                if let StreamLine::Token(token) = provider.parse_stream_data(&data) {
+                    full_text.push_str(&token);
+                    pacer.push(window, &token)?;
+                }
+            }
+            Ok(None) => {}
+            Err(Interrupted(partial)) => {
+                pacer.finish(window).await?;
+                tracing::warn!(partial_len = partial.len(), "stream closed mid-message");
+                let err = AppError::Parse("stream closed partway through an event".into());
+                window.emit("ai-error", serde_json::to_value(&err).unwrap_or_default())?;
+                return Err(err);
+            }
+        }
+    }
+    pacer.finish(window).await?;
+    // Tokens already went out as streamed; `ai-done` and the stored reply
+    // carry the processed text.
+    let full_text = post_process(&window.app_handle(), &turn.prompt, full_text)?;
+
+    tracing::info!(reply_len = full_text.len(), "stream finished");
+    record_latency(&window.state::<WriteQueue>(), provider.model(), &full_text, started)?;
+    finish_turn(&window.app_handle(), provider, turn, &full_text, started)?;
+    window.emit("ai-done", &full_text)?;
+    Ok(full_text)
+}
+
+#[derive(serde::Serialize)]
+pub struct AiResponse {
+    pub text: String,
+    /// Older messages were left out of the prompt to fit the context window.
+    pub context_truncated: bool,
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(window, message, params), fields(message_len = message.len()), err)]
+pub async fn generate_ai_response(
+    window: tauri::Window,
+    conversation_id: String,
+    message: String,
+    provider: Option<ProviderKind>,
+    request_id: Option<String>,
+    params: Option<GenerationParams>,
+) -> Result<AiResponse, AppError> {
+    let app = window.app_handle();
+    let turn = start_turn(&app, conversation_id.clone(), message, None, params)?;
+    let context_truncated = turn.context_truncated;
+    let model = turn.model.clone();
+    let kind = provider.unwrap_or(app.state::<ProviderConfig>().default_kind);
+    let text = match complete(&window, turn, provider, request_id).await {
+        Err(e) if kind == ProviderKind::HuggingFace => return Err(models::explain_not_found(&app, &model, e).await),
+        result => result?,
+    };
+    // The draft was the message just answered.
+    let db = app.state::<db::Db>();
+    let conn = db.0.lock()?;
+    db::clear_draft(&conn, &conversation_id)?;
+    Ok(AiResponse { text, context_truncated })
+}
+
+/// A reply requested as JSON. `text` is the reply as generated and stored;
+/// `parsed` is its value, taken from a repair attempt if the original didn't
+/// parse, and `None` if neither did.
+#[derive(serde::Serialize)]
+pub struct JsonReply {
+    pub text: String,
+    pub parsed: Option<serde_json::Value>,
+}
+
+/// Parses a reply as JSON, tolerating a surrounding Markdown code fence.
+fn parse_json_reply(text: &str) -> Option<serde_json::Value> {
+    let text = text.trim();
+    let text = text
+        .strip_prefix("```json")
+        .or_else(|| text.strip_prefix("```"))
+        .and_then(|rest| rest.strip_suffix("```"))
+        .unwrap_or(text);
+    serde_json::from_str(text.trim()).ok()
+}
+
+/// Like `generate_ai_response`, but asks for JSON output and parses it. A
+/// reply that doesn't parse gets one repair request, outside the
+/// conversation, asking the model to fix it.
+#[tauri::command]
+#[tracing::instrument(skip(window, message, params), fields(message_len = message.len()), err)]
+pub async fn generate_json_response(
+    window: tauri::Window,
+    conversation_id: String,
+    message: String,
+    provider: Option<ProviderKind>,
+    request_id: Option<String>,
+    params: Option<GenerationParams>,
+) -> Result<JsonReply, AppError> {
+    let params = GenerationParams {
+        response_format: ResponseFormat::Json,
+        ..params.unwrap_or_default()
+    };
+    let app = window.app_handle();
+    let turn = start_turn(&app, conversation_id, message, None, Some(params.clone()))?;
+    let model = turn.model.clone();
+    let text = complete(&window, turn, provider, request_id).await?;
+    if let Some(parsed) = parse_json_reply(&text) {
+        return Ok(JsonReply { text, parsed: Some(parsed) });
+    }
+    let config = app.state::<ConfigStore>().get()?;
+    if config.offline_mode {
+        return Ok(JsonReply { text, parsed: None });
+    }
+
+    tracing::info!("reply is not valid JSON, asking for a repair");
+    let provider_config = app.state::<ProviderConfig>();
+    let kind = provider.unwrap_or(provider_config.default_kind);
+    let provider = providers::build(kind, &provider_config, app.state::<HttpClient>().get()?, &model)?;
+    let repair = [PromptMessage::new(Role::User, &format!("{}{}", JSON_REPAIR_INSTRUCTION, text))];
+    let repaired = ask(&app, provider.as_ref(), &repair, &params, config.max_retries).await?;
+    record_usage(&app.state::<WriteQueue>(), provider.model(), &repair[0].content, &repaired)?;
+    Ok(JsonReply {
+        parsed: parse_json_reply(&repaired),
+        text,
+    })
+}
+
+/// A tool call, if the whole reply is `{"tool": name, "args": {...}}`.
+fn parse_tool_call(reply: &str) -> Option<(String, serde_json::Value)> {
+    let value = parse_json_reply(reply)?;
+    let name = value.get("tool")?.as_str()?.to_string();
+    Some((name, value.get("args").cloned().unwrap_or_default()))
+}
+
+/// Payload of the `ai-tool-call` event.
+#[derive(serde::Serialize, Clone)]
+struct ToolCall<'a> {
+    tool: &'a str,
+    args: &'a serde_json::Value,
+}
+
+async fn ask(
+    app: &tauri::AppHandle,
+    provider: &dyn AiProvider,
+    messages: &[PromptMessage],
+    params: &GenerationParams,
+    max_retries: u32,
+) -> Result<String, AppError> {
+    app.state::<RateLimiter>().acquire().await?;
+    retry::with_backoff(max_retries, || provider.chat(messages, params))
+        .await
+        .map_err(|e| app.state::<HttpClient>().explain(e.into()))
+}
+
+/// Answers `message` like `generate_ai_response`, but lets the model call the
+/// local tools in `tools` first. Each call is announced with an
+/// `ai-tool-call` event and its result, or error, is handed back to the
+/// model. After `MAX_TOOL_CALLS` the model is told to answer with what it
+/// has. Only the message and the final answer are stored.
+#[tauri::command]
+#[tracing::instrument(skip(window, message), fields(message_len = message.len()), err)]
+pub async fn generate_with_tools(window: tauri::Window, conversation_id: String, message: String) -> Result<String, AppError> {
+    let app = window.app_handle();
+    let turn = start_turn(&app, conversation_id, message, None, None)?;
+    let config = app.state::<ConfigStore>().get()?;
+    if config.offline_mode {
+        return complete_offline(&app, turn, None).await;
+    }
+    let active = app.state::<ActiveQueries>();
+    let _active = active.enter();
+    let started = Instant::now();
+    let result = async {
+        let provider_config = app.state::<ProviderConfig>();
+        let http = app.state::<HttpClient>();
+        let provider = providers::build(provider_config.default_kind, &provider_config, http.get()?, &turn.model)?;
+        app.state::<Connectivity>().ensure_reachable(&http.get()?, provider.endpoint()).await?;
+
+        let mut messages = turn.messages.clone();
+        let after_system_prompt = usize::from(messages.first().is_some_and(|m| m.role == Role::System));
+        messages.insert(after_system_prompt, PromptMessage::new(Role::System, &tools::instructions()));
+        let mut calls = 0;
+        let reply = loop {
+            let reply = ask(&app, provider.as_ref(), &messages, &turn.params, config.max_retries).await?;
+            let Some((name, args)) = parse_tool_call(&reply) else {
+                break reply;
+            };
+            messages.push(PromptMessage::new(Role::Assistant, &reply));
+            if calls == MAX_TOOL_CALLS {
+                messages.push(PromptMessage::new(Role::User, TOOLS_EXHAUSTED));
+                break ask(&app, provider.as_ref(), &messages, &turn.params, config.max_retries).await?;
+            }
+            calls += 1;
+            window.emit("ai-tool-call", ToolCall { tool: &name, args: &args })?;
+            let result = tools::dispatch(&app, &name, args)
+                .await
+                .unwrap_or_else(|e| serde_json::json!({ "error": e.to_string() }));
+            messages.push(PromptMessage::new(Role::User, &format!("Result of {}: {}", name, result)));
+        };
+        Ok::<_, AppError>((provider, reply, calls))
+    }
+    .await;
+    let (provider, reply, calls) = match result {
+        Ok(done) => done,
+        Err(e) => {
+            record_audit(&app.state::<WriteQueue>(), &turn.model, &turn.prompt, "", started, "error")?;
+            return Err(e);
+        }
+    };
+    let reply = post_process(&app, &turn.prompt, reply)?;
+    tracing::info!(tool_calls = calls, reply_len = reply.len(), "tool-assisted generation finished");
+    record_audit(&app.state::<WriteQueue>(), provider.model(), &turn.prompt, &reply, started, "ok")?;
+    finish_turn(&app, provider, turn, &reply, started)?;
+    Ok(reply)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub fn cancel_ai_response(in_flight: tauri::State<'_, InFlightRequests>, request_id: String) -> Result<bool, AppError> {
+    Ok(in_flight.cancel(&request_id))
+}
+
+/// `stream_granularity` defaults to forwarding provider tokens as they come.
+#[tauri::command]
+#[tracing::instrument(skip(window, message, params), fields(message_len = message.len()), err)]
+pub async fn generate_ai_response_stream(
+    window: tauri::Window,
+    conversation_id: String,
+    message: String,
+    params: Option<GenerationParams>,
+    stream_granularity: Option<StreamGranularity>,
+) -> Result<String, AppError> {
+    let turn = start_turn(&window.app_handle(), conversation_id, message, None, params)?;
+    stream_completion(&window, turn, stream_granularity.unwrap_or_default()).await
+}
+
+/// Replaces the conversation's latest assistant reply with a fresh one made
+/// from the same context at a slightly higher temperature. The old reply is
+/// only removed once the new one is stored, so a failure leaves it in
+/// place. If the latest message is the user's (no reply yet), this just
+/// answers it. With
+/// `stream: true` the reply is streamed with the usual `ai-token`/`ai-done`
+/// events.
+#[tauri::command]
+#[tracing::instrument(skip(window, params), err)]
+pub async fn regenerate_response(
+    window: tauri::Window,
+    conversation_id: String,
+    params: Option<GenerationParams>,
+    stream: Option<bool>,
+) -> Result<String, AppError> {
+    let app = window.app_handle();
+    let (model, mut params) = resolve_settings(&app, &conversation_id, params)?;
+    let base = params.temperature.unwrap_or(DEFAULT_TEMPERATURE);
+    params.temperature = Some((base + REGENERATE_TEMPERATURE_STEP).min(2.0));
+    params.validate()?;
+
+    let (turn, old_reply) = {
+        let config = app.state::<ConfigStore>().get()?;
+        let max_context_messages = config.max_context_messages;
+        let db = app.state::<db::Db>();
+        let conn = db.0.lock()?;
+        let mut history = db::load_recent_messages(&conn, &conversation_id, max_context_messages + 2)?;
+        sidecar::rehydrate_all(&app.state::<DataDir>().0, &mut history);
+        let old_reply = match history.last() {
+            Some(last) if last.sender != Role::User => history.pop().map(|reply| reply.id),
+            _ => None,
+        };
+        let Some(user_message) = history.pop().filter(|m| m.sender == Role::User) else {
+            return Err(AppError::InvalidParams("conversation has no user message to answer".into()));
+        };
+        let skip = history.len().saturating_sub(max_context_messages);
+        let system_prompt = db::get_system_prompt(&conn, &conversation_id)?;
+        let messages = context::build_messages(system_prompt.as_deref(), &history[skip..], &user_message.content);
+        let turn = Turn::new(conversation_id, user_message.content, messages, model, params, config.context_window);
+        (turn, old_reply)
+    };
+
+    let reply = if stream.unwrap_or(false) {
+        stream_completion(&window, turn, StreamGranularity::Token).await?
+    } else {
+        complete(&window, turn, None, None).await?
+    };
+    if let Some(id) = old_reply {
+        let db = app.state::<db::Db>();
+        let conn = db.0.lock()?;
+        db::delete_message(&conn, &id)?;
+    }
+    Ok(reply)
+}
+
+/// Re-asks a user message with `new_content` in a branch of its
+/// conversation, leaving the original alone. The branch holds everything
+/// before the message, then the edited message and a fresh reply. Returns
+/// the branch's conversation id; if the resend fails, the branch is removed.
+#[tauri::command]
+#[tracing::instrument(skip(window, new_content), err)]
+pub async fn edit_and_resend(window: tauri::Window, message_id: String, new_content: String) -> Result<String, AppError> {
+    if new_content.trim().is_empty() {
+        return Err(AppError::InvalidParams("message must not be empty".into()));
+    }
+    let app = window.app_handle();
+    let branch_id = uuid::Uuid::new_v4().to_string();
+    {
+        let db = app.state::<db::Db>();
+        let conn = db.0.lock()?;
+        let message = db::get_message(&conn, &message_id)?
+            .ok_or_else(|| AppError::InvalidParams(format!("no message with id {}", message_id)))?;
+        if message.sender != Role::User {
+            return Err(AppError::InvalidParams("only user messages can be edited and resent".into()));
+        }
+        db::branch_conversation(&conn, &message_id, &branch_id)?;
+        // The branch ends with its copy of the message, which the edit replaces.
+        if let Some(copy) = db::load_recent_messages(&conn, &branch_id, 1)?.pop() {
+            db::delete_message(&conn, &copy.id)?;
+        }
+    }
+    let result = match start_turn(&app, branch_id.clone(), new_content, None, None) {
+        Ok(turn) => complete(&window, turn, None, None).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        // A failed resend shouldn't leave a half-made branch behind.
+        let db = app.state::<db::Db>();
+        let conn = db.0.lock()?;
+        db::delete_conversation(&conn, &branch_id)?;
+        return Err(e);
+    }
+    Ok(branch_id)
+}
+
+/// Answers `message` with the contents of `file_paths` included as context.
+/// Only the message itself is saved to the conversation (`"default"` unless
+/// one is given).
+#[tauri::command]
+#[tracing::instrument(skip(window, message), fields(message_len = message.len()), err)]
+pub async fn generate_with_context(
+    window: tauri::Window,
+    message: String,
+    file_paths: Vec<String>,
+    conversation_id: Option<String>,
+) -> Result<String, AppError> {
+    let app = window.app_handle();
+    let limit = app.state::<ConfigStore>().get()?.max_attachment_bytes;
+    let context = attachments::read_context(&file_paths, limit)?;
+    let conversation_id = conversation_id.unwrap_or_else(|| "default".to_string());
+    let turn = start_turn(&app, conversation_id, message, Some(&context), None)?;
+    complete(&window, turn, None, None).await
+}
+
+/// Fills the saved template `name` from `vars` (`input` for `{{input}}`)
+/// and sends the result as a message in `conversation_id` (`"default"`
+/// unless given).
+#[tauri::command]
+#[tracing::instrument(skip(window, vars, params), err)]
+pub async fn generate_from_template(
+    window: tauri::Window,
+    name: String,
+    vars: HashMap<String, String>,
+    conversation_id: Option<String>,
+    params: Option<GenerationParams>,
+) -> Result<String, AppError> {
+    let app = window.app_handle();
+    let body = {
+        let db = app.state::<db::Db>();
+        let conn = db.0.lock()?;
+        db::get_template(&conn, name.trim())?
+    };
+    let body = body.ok_or_else(|| AppError::InvalidParams(format!("no template named {:?}", name)))?;
+    let message = templates::render(&body, &vars)?;
+    let conversation_id = conversation_id.unwrap_or_else(|| "default".to_string());
+    let turn = start_turn(&app, conversation_id, message, None, params)?;
+    complete(&window, turn, None, None).await
+}
+
+/// Runs each prompt on its own, without conversation history, at most
+/// `concurrency` at a time. Results come back in the order of `prompts`, and
+/// a failed prompt doesn't stop the rest.
+///
+/// With a `batch_id`, `cancel_batch` stops any prompt that hasn't started
+/// yet; those come back as `cancelled` while the ones already running finish.
+#[tauri::command]
+#[tracing::instrument(skip(app, prompts, params), fields(prompts = prompts.len()), err)]
+pub async fn generate_batch(
+    app: tauri::AppHandle,
+    prompts: Vec<String>,
+    concurrency: usize,
+    params: Option<GenerationParams>,
+    batch_id: Option<String>,
+) -> Result<Vec<Result<String, AppError>>, AppError> {
+    let params = params.unwrap_or_default();
+    params.validate()?;
+    let blocklist = app.state::<Blocklist>();
+    if let Some(blocked) = prompts.iter().find_map(|prompt| blocklist.check(prompt).err()) {
+        return Err(blocked);
+    }
+    if app.state::<ConfigStore>().get()?.offline_mode {
+        return Ok(prompts.iter().map(|prompt| Ok(offline_reply(prompt))).collect());
+    }
+    let provider_config = app.state::<ProviderConfig>();
+    let client = app.state::<HttpClient>().get()?;
+    let model = current_model(&app.state::<ModelState>())?;
+    let provider: Arc<dyn AiProvider> =
+        providers::build(provider_config.default_kind, &provider_config, client, &model)?.into();
+    let max_retries = app.state::<ConfigStore>().get()?.max_retries;
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(concurrency.max(1)));
+    let batches = app.state::<RunningBatches>();
+    let registration = batch_id.map(|id| batches.0.register(id));
+    let token = registration.as_ref().map(|r| r.token().clone()).unwrap_or_default();
+
+    let tasks: Vec<_> = prompts
+        .into_iter()
+        .map(|prompt| {
+            let app = app.clone();
+            let provider = provider.clone();
+            let semaphore = semaphore.clone();
+            let params = params.clone();
+            let token = token.clone();
+            tauri::async_runtime::spawn(async move {
+                let _permit = tokio::select! {
+                    biased;
+                    _ = token.cancelled() => return Err(AppError::Cancelled),
+                    permit = semaphore.acquire() => permit.map_err(|_| AppError::Cancelled)?,
+                };
+                app.state::<RateLimiter>().acquire().await?;
+                let active = app.state::<ActiveQueries>();
+                let _active = active.enter();
+
+                let messages = context::build_messages(None, &[], &prompt);
+                let prompt = context::format_prompt(&messages);
+                let reply = retry::with_backoff(max_retries, || provider.chat(&messages, &params))
+                    .await
+                    .map_err(|e| app.state::<HttpClient>().explain(e.into()))?;
+                record_usage(&app.state::<WriteQueue>(), provider.model(), &prompt, &reply)?;
+                post_process(&app, &prompt, reply)
+            })
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        results.push(match task.await {
+            Ok(result) => result,
+            Err(tauri::Error::JoinError(e)) if e.is_panic() => {
+                tracing::error!(error = %e, "batch prompt panicked");
+                Err(AppError::Internal(format!("batch prompt panicked: {}", e)))
+            }
+            Err(_) => Err(AppError::Cancelled),
+        });
+    }
+    drop(registration);
+    Ok(results)
+}
+
+/// `generate_batch` in the background. Returns a task id at once, which is
+/// also the batch id for `cancel_batch`; the results arrive with
+/// `task-complete`.
+#[tauri::command]
+#[tracing::instrument(skip(app, prompts, params), fields(prompts = prompts.len()), err)]
+pub fn start_batch(
+    app: tauri::AppHandle,
+    prompts: Vec<String>,
+    concurrency: usize,
+    params: Option<GenerationParams>,
+) -> Result<String, AppError> {
+    let total = prompts.len();
+    let batch_app = app.clone();
+    Ok(tasks::spawn(
+        &app,
+        TaskKind::Batch,
+        |task_id| generate_batch(batch_app, prompts, concurrency, params, Some(task_id)),
+        move |results: &Vec<Result<String, AppError>>| {
+            let succeeded = results.iter().filter(|result| result.is_ok()).count();
+            format!("{} of {} prompts answered", succeeded, total)
+        },
+    ))
+}
+
+/// Stops a batch started with `batch_id` from dispatching more prompts.
+/// Returns `false` if no such batch is running.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub fn cancel_batch(batches: tauri::State<'_, RunningBatches>, batch_id: String) -> Result<bool, AppError> {
+    Ok(batches.0.cancel(&batch_id))
+}
@@ -0,0 +1,89 @@
+use std::time::Instant;
+
+use tauri::Manager;
+
+use crate::config::ModelState;
+use crate::credentials;
+use crate::error::AppError;
+use crate::generation::current_model;
+use crate::http::HttpClient;
+use crate::providers::{GenerationParams, HuggingFace, ProviderError, ProviderSettings};
+
+#[derive(serde::Serialize, Debug, Clone, Default)]
+pub struct HealthStatus {
+    /// The endpoint answered at all.
+    reachable: bool,
+    /// The token was accepted (false on 401/403 or when none is set).
+    token_valid: bool,
+    /// The model is ready to serve (false on 503 while it loads).
+    model_loaded: bool,
+    latency_ms: u64,
+}
+
+/// Probes the current Hugging Face model with a minimal request. Failures are
+/// reported in the returned status rather than as errors, so the UI can show
+/// what exactly is wrong.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn check_ai_health(app: tauri::AppHandle) -> Result<HealthStatus, AppError> {
+    let client = app.state::<HttpClient>().get()?;
+    let Ok(token) = credentials::require_token() else {
+        return Ok(HealthStatus::default());
+    };
+    let provider = HuggingFace::new(client, &current_model(&app.state::<ModelState>())?, token);
+
+    let started = Instant::now();
+    let status = provider.probe().await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+    let Ok(status) = status else {
+        return Ok(HealthStatus {
+            latency_ms,
+            ..HealthStatus::default()
+        });
+    };
+    let token_valid = status != reqwest::StatusCode::UNAUTHORIZED && status != reqwest::StatusCode::FORBIDDEN;
+    Ok(HealthStatus {
+        reachable: true,
+        token_valid,
+        model_loaded: token_valid && status != reqwest::StatusCode::SERVICE_UNAVAILABLE,
+        latency_ms,
+    })
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct ProviderTestResult {
+    /// The server answered, even if with an error.
+    reachable: bool,
+    /// The test prompt got a reply.
+    ok: bool,
+    latency_ms: u64,
+    error: Option<AppError>,
+}
+
+/// Sends a one-token prompt through a provider built from `settings`, which
+/// are used only for this request and not saved. Like `check_ai_health`,
+/// failures come back in the result rather than as errors.
+#[tauri::command]
+#[tracing::instrument(skip(app, settings), fields(kind = ?settings.kind), err)]
+pub async fn test_provider(app: tauri::AppHandle, settings: ProviderSettings) -> Result<ProviderTestResult, AppError> {
+    let provider = match settings.build(app.state::<HttpClient>().get()?) {
+        Ok(provider) => provider,
+        Err(e) => return Ok(ProviderTestResult { reachable: false, ok: false, latency_ms: 0, error: Some(e) }),
+    };
+    let params = GenerationParams {
+        max_new_tokens: Some(1),
+        ..Default::default()
+    };
+    let started = Instant::now();
+    let result = provider.generate("ping", &params).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+    Ok(match result {
+        Ok(_) => ProviderTestResult { reachable: true, ok: true, latency_ms, error: None },
+        Err(e) => ProviderTestResult {
+            reachable: !matches!(&e, ProviderError::Network(err) if err.is_connect() || err.is_timeout()),
+            ok: false,
+            latency_ms,
+            error: Some(e.into()),
+        },
+    })
+}
@@ -0,0 +1,94 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::error::AppError;
+
+/// The HTTP client shared by all outgoing requests. It's rebuilt when the
+/// network settings change; clones are cheap and share the connection pool.
+///
+/// Credentials are attached per request (`bearer_auth`), never as default
+/// headers, because the same client talks to every provider.
+pub struct HttpClient(Mutex<Inner>);
+
+struct Inner {
+    client: reqwest::Client,
+    /// The configured proxy with any credentials stripped, for error messages.
+    proxy: Option<String>,
+}
+
+impl HttpClient {
+    pub fn new(config: &Config) -> Result<Self, AppError> {
+        Ok(HttpClient(Mutex::new(build(config)?)))
+    }
+
+    pub fn get(&self) -> Result<reqwest::Client, AppError> {
+        Ok(self.0.lock()?.client.clone())
+    }
+
+    pub fn rebuild(&self, config: &Config) -> Result<(), AppError> {
+        *self.0.lock()? = build(config)?;
+        Ok(())
+    }
+
+    /// With a proxy configured, a connection failure almost always means the
+    /// proxy itself is unreachable, so say that instead of a generic network
+    /// error.
+    pub fn explain(&self, err: AppError) -> AppError {
+        let proxy = self.0.lock().ok().and_then(|inner| inner.proxy.clone());
+        match (err, proxy) {
+            (AppError::Network(e), Some(proxy)) if e.is_connect() => AppError::ProxyUnreachable(proxy),
+            (err, _) => err,
+        }
+    }
+}
+
+fn build(config: &Config) -> Result<Inner, AppError> {
+    let timeout = Duration::from_secs(config.timeout_secs.max(1));
+    let mut builder = reqwest::Client::builder()
+        .timeout(timeout)
+        .connect_timeout(timeout)
+        .pool_idle_timeout(Duration::from_secs(90))
+        .pool_max_idle_per_host(4)
+        .tcp_keepalive(Duration::from_secs(60));
+
+    let mut proxy_display = None;
+    if let Some(proxy_url) = config.proxy_url.as_deref().filter(|u| !u.trim().is_empty()) {
+        let (proxy, display) = parse_proxy(proxy_url.trim())?;
+        builder = builder.proxy(proxy);
+        proxy_display = Some(display);
+    }
+
+    Ok(Inner {
+        client: builder.build()?,
+        proxy: proxy_display,
+    })
+}
+
+/// Validates an `http://` or `https://` proxy URL, applying any
+/// `user:password@` credentials it embeds as basic auth. Returns the proxy
+/// and the URL with credentials removed.
+fn parse_proxy(proxy_url: &str) -> Result<(reqwest::Proxy, String), AppError> {
+    let invalid = |reason: String| AppError::InvalidParams(format!("invalid proxy URL: {}", reason));
+    let mut url = reqwest::Url::parse(proxy_url).map_err(|e| invalid(e.to_string()))?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(invalid("expected http://host:port or https://host:port".into()));
+    }
+
+    let username = url.username().to_string();
+    let password = url.password().unwrap_or_default().to_string();
+    // Credentials are passed explicitly below; failing here is impossible
+    // for http(s) URLs that have a host.
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+
+    let mut proxy = reqwest::Proxy::all(url.as_str()).map_err(|e| invalid(e.to_string()))?;
+    if !username.is_empty() {
+        proxy = proxy.basic_auth(&percent_decode(&username), &percent_decode(&password));
+    }
+    Ok((proxy, url.to_string()))
+}
+
+fn percent_decode(text: &str) -> String {
+    percent_encoding::percent_decode_str(text).decode_utf8_lossy().into_owned()
+}
@@ -0,0 +1,58 @@
+use base64::Engine;
+use tauri::Manager;
+
+use crate::config::ConfigStore;
+use crate::credentials;
+use crate::data_dir::DataDir;
+use crate::error::AppError;
+use crate::http::HttpClient;
+use crate::providers::HuggingFace;
+use crate::rate_limit::RateLimiter;
+use crate::retry;
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct ImageResult {
+    /// Where the image was saved under the data directory. Prefer showing
+    /// the file over decoding `base64`.
+    path: String,
+    mime_type: String,
+    base64: String,
+}
+
+/// Generates an image with the Hugging Face text-to-image model `model` and
+/// saves it to `images/` in the data directory. A model that is still
+/// loading is retried like text generation.
+#[tauri::command]
+#[tracing::instrument(skip(app, prompt), fields(prompt_len = prompt.len()), err)]
+pub async fn generate_image(app: tauri::AppHandle, prompt: String, model: String) -> Result<ImageResult, AppError> {
+    if prompt.trim().is_empty() {
+        return Err(AppError::InvalidParams("prompt must not be empty".into()));
+    }
+    let http = app.state::<HttpClient>();
+    let provider = HuggingFace::new(http.get()?, &model, credentials::require_token()?);
+    let max_retries = app.state::<ConfigStore>().get()?.max_retries;
+    app.state::<RateLimiter>().acquire().await?;
+    let (bytes, mime_type) = retry::with_backoff(max_retries, || provider.text_to_image(&prompt))
+        .await
+        .map_err(|e| http.explain(e.into()))?;
+
+    let dir = app.state::<DataDir>().0.join("images");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.{}", uuid::Uuid::new_v4(), extension(&mime_type)));
+    std::fs::write(&path, &bytes)?;
+    tracing::info!(bytes = bytes.len(), path = %path.display(), "image saved");
+    Ok(ImageResult {
+        path: path.display().to_string(),
+        base64: base64::engine::general_purpose::STANDARD.encode(&bytes),
+        mime_type,
+    })
+}
+
+fn extension(mime_type: &str) -> &str {
+    match mime_type {
+        "image/jpeg" => "jpg",
+        "image/webp" => "webp",
+        "image/gif" => "gif",
+        _ => "png",
+    }
+}
@@ -0,0 +1,28 @@
+use std::path::Path;
+
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+/// Keeps the background log writer alive; dropping it stops file logging.
+pub struct LogGuard(#[allow(dead_code)] WorkerGuard);
+
+/// Logs to stdout and to a daily-rotated file under `<data_dir>/logs`. The
+/// level (or a full filter directive such as `aios=debug`) comes from the
+/// `log_level` env var, or `AIOS_LOG_LEVEL` if that isn't set, and defaults
+/// to `info`.
+pub fn init(data_dir: &Path) -> LogGuard {
+    let filter = std::env::var("log_level")
+        .or_else(|_| std::env::var("AIOS_LOG_LEVEL"))
+        .ok()
+        .and_then(|level| EnvFilter::try_new(level).ok())
+        .unwrap_or_else(|| EnvFilter::new("info"));
+    let (file, guard) = tracing_appender::non_blocking(tracing_appender::rolling::daily(data_dir.join("logs"), "aios.log"));
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_subscriber::fmt::layer().with_ansi(false).with_writer(file))
+        .init();
+    LogGuard(guard)
+}
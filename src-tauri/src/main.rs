@@ -1,87 +1,796 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use sysinfo::{System, SystemExt, CpuExt};
+mod active;
+mod attachments;
+mod battery;
+mod benchmark;
+mod blocklist;
+mod cache;
+mod cancel;
+mod config;
+mod connectivity;
+mod context;
+mod conversations;
+mod credentials;
+mod data_dir;
+mod db;
+mod embeddings;
+mod error;
+mod export;
+mod generation;
+mod health;
+mod http;
+mod images;
+mod logging;
+mod migrations;
+mod models;
+mod outbox;
+mod performance;
+mod postprocess;
+mod providers;
+mod rate_limit;
+mod retry;
+mod setup;
+mod shortcuts;
+mod sidecar;
+mod smoothing;
+mod tasks;
+mod templates;
+mod timestamps;
+mod tokens;
+mod tools;
+mod transcription;
+mod warmup;
+mod window_state;
+mod writer;
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tauri::{ClipboardManager, Manager};
+
+use active::ActiveQueries;
+use blocklist::Blocklist;
+use cache::ResponseCache;
+use cancel::{InFlightRequests, RunningBatches};
+use config::{Config, ConfigStore, ModelState};
+use connectivity::Connectivity;
+use data_dir::DataDir;
+use error::AppError;
+use export::ExportFormat;
+use http::HttpClient;
+use models::{ModelListCache, UnmatchedModels};
+use outbox::Outbox;
+use performance::{GpuMonitor, LaunchTime, NetworkSnapshot, PerformanceHistory, PerformanceStream, SharedSystem};
+use providers::ProviderConfig;
+use rate_limit::{RateLimitStatus, RateLimiter};
+use warmup::WarmModels;
+use writer::{Flusher, WriteQueue};
+
+/// Who a message is from. Stored and sent to the frontend in lowercase.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+    System,
+}
+
+impl Role {
+    fn as_str(self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Assistant => "assistant",
+            Role::System => "system",
+        }
+    }
+
+    /// Speaker name used when formatting prompts and exports.
+    fn label(self) -> &'static str {
+        match self {
+            Role::User => "User",
+            Role::Assistant => "Assistant",
+            Role::System => "System",
+        }
+    }
+}
+
+impl rusqlite::types::ToSql for Role {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl rusqlite::types::FromSql for Role {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        match value.as_str()? {
+            "user" => Ok(Role::User),
+            "assistant" => Ok(Role::Assistant),
+            "system" => Ok(Role::System),
+            other => Err(rusqlite::types::FromSqlError::Other(
+                format!("unknown message role {:?}", other).into(),
+            )),
+        }
+    }
+}
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct ChatMessage {
     id: String,
     content: String,
-    sender: String,
+    sender: Role,
     timestamp: String,
+    conversation_id: String,
+    /// Pinned messages are kept by `clear_chat_history` unless forced.
+    #[serde(default)]
+    pinned: bool,
+    /// Highlighted excerpt, only set on search results.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    snippet: Option<String>,
+    /// How an assistant reply was generated; only loaded by `get_chat_history`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    meta: Option<MessageMeta>,
+    /// Sidecar file with the full content when `content` is only a preview.
+    #[serde(skip)]
+    content_file: Option<String>,
+    /// Set when `content` is a preview of a longer message.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    truncated: bool,
 }
 
+/// Generation details stored alongside an assistant reply.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct MessageMeta {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt_tokens: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    completion_tokens: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_ms: Option<u64>,
+}
+
+impl ChatMessage {
+    fn new(conversation_id: &str, content: String, sender: Role) -> Self {
+        ChatMessage {
+            id: uuid::Uuid::new_v4().to_string(),
+            content,
+            sender,
+            timestamp: timestamps::now(),
+            conversation_id: conversation_id.to_string(),
+            pinned: false,
+            snippet: None,
+            meta: None,
+            content_file: None,
+            truncated: false,
+        }
+    }
+}
+
+fn now_timestamp() -> String {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Saved messages, with oversized ones read back in full from their sidecar
+/// files. With `previews_only` those stay as their stored preview, marked
+/// `truncated`. Timestamps are in the `timestamp_display` zone.
 #[tauri::command]
-async fn get_chat_history() -> Result<Vec<ChatMessage>, String> {
-    Ok(vec![])
+#[tracing::instrument(skip_all, err)]
+async fn get_chat_history(
+    db: tauri::State<'_, db::Db>,
+    data_dir: tauri::State<'_, DataDir>,
+    config: tauri::State<'_, ConfigStore>,
+    conversation_id: Option<String>,
+    previews_only: Option<bool>,
+) -> Result<Vec<ChatMessage>, AppError> {
+    let mut messages = {
+        let conn = db.0.lock()?;
+        db::load_messages(&conn, conversation_id.as_deref())?
+    };
+    if !previews_only.unwrap_or(false) {
+        sidecar::rehydrate_all(&data_dir.0, &mut messages);
+    }
+    timestamps::localize(&mut messages, config.get()?.timestamp_display);
+    Ok(messages)
 }
 
+/// All conversations, most recently updated first, with `updated_at` in the
+/// `timestamp_display` zone.
 #[tauri::command]
-async fn generate_ai_response(message: String) -> Result<String, String> {
-    let client = reqwest::Client::new();
-    let api_url = "https://api-inference.huggingface.co/models/gpt2"; // Replace with your desired model
-    let hf_token = std::env::var("HF_TOKEN").map_err(|e| format!("HF_TOKEN not set: {}", e))?;
+#[tracing::instrument(skip_all, err)]
+fn get_conversations(db: tauri::State<'_, db::Db>, config: tauri::State<'_, ConfigStore>) -> Result<Vec<db::ConversationSummary>, AppError> {
+    let display = config.get()?.timestamp_display;
+    let conn = db.0.lock()?;
+    let mut conversations = db::load_conversations(&conn)?;
+    for conversation in &mut conversations {
+        conversation.updated_at = timestamps::format_timestamp(&conversation.updated_at, display);
+    }
+    Ok(conversations)
+}
 
-    let mut headers = reqwest::header::HeaderMap::new();
-    headers.insert("Authorization", format!("Bearer {}", hf_token).parse().unwrap());
+/// Starts a new conversation holding a copy of the history up to and
+/// including `from_message_id`, and returns its id. The original is left
+/// untouched.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn branch_conversation(db: tauri::State<'_, db::Db>, from_message_id: String) -> Result<String, AppError> {
+    let conn = db.0.lock()?;
+    let branch_id = uuid::Uuid::new_v4().to_string();
+    if !db::branch_conversation(&conn, &from_message_id, &branch_id)? {
+        return Err(AppError::InvalidParams(format!("no message with id {}", from_message_id)));
+    }
+    Ok(branch_id)
+}
 
-    let payload = serde_json::json!({
-        "inputs": message
-    });
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn set_system_prompt(db: tauri::State<'_, db::Db>, conversation_id: String, prompt: String) -> Result<(), AppError> {
+    let conn = db.0.lock()?;
+    Ok(db::set_system_prompt(&conn, &conversation_id, &prompt)?)
+}
 
-    let res = client.post(api_url)
-        .headers(headers)
-        .json(&payload)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to send request to Hugging Face API: {}", e))?;
-
-    if res.status().is_success() {
-        let response_json: serde_json::Value = res.json().await.map_err(|e| format!("Failed to parse response JSON: {}", e))?;
-        // Assuming the response is an array of objects with a 'generated_text' field
-        if let Some(generated_text) = response_json[0]["generated_text"].as_str() {
-            Ok(generated_text.to_string())
-        } else {
-            Err("Generated text not found in response.".into())
-        }
-    } else {
-        let status = res.status();
-        let error_text = res.text().await.map_err(|e| format!("Failed to get error text: {}", e))?;
-        Err(format!("Hugging Face API error: {} - {}", status, error_text))
+/// Overrides the model and sampling parameters for one conversation.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn set_conversation_settings(
+    db: tauri::State<'_, db::Db>,
+    conversation_id: String,
+    settings: db::ConversationSettings,
+) -> Result<(), AppError> {
+    settings.params.validate()?;
+    let conn = db.0.lock()?;
+    Ok(db::set_conversation_settings(&conn, &conversation_id, &settings)?)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn get_conversation_settings(
+    db: tauri::State<'_, db::Db>,
+    conversation_id: String,
+) -> Result<db::ConversationSettings, AppError> {
+    let conn = db.0.lock()?;
+    Ok(db::get_conversation_settings(&conn, &conversation_id)?)
+}
+
+/// Keeps the unsent input of a conversation so it survives a crash. The
+/// frontend calls this debounced while the user types.
+#[tauri::command]
+#[tracing::instrument(skip(db, text), err)]
+fn save_draft(db: tauri::State<'_, db::Db>, conversation_id: String, text: String) -> Result<(), AppError> {
+    let conn = db.0.lock()?;
+    Ok(db::save_draft(&conn, &conversation_id, &text, &now_timestamp())?)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn load_draft(db: tauri::State<'_, db::Db>, conversation_id: String) -> Result<Option<String>, AppError> {
+    let conn = db.0.lock()?;
+    Ok(db::load_draft(&conn, &conversation_id)?)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(db, body), err)]
+fn save_template(db: tauri::State<'_, db::Db>, name: String, body: String) -> Result<(), AppError> {
+    if name.trim().is_empty() || body.trim().is_empty() {
+        return Err(AppError::InvalidParams("template name and body must not be empty".into()));
     }
+    let conn = db.0.lock()?;
+    Ok(db::save_template(&conn, name.trim(), &body, &now_timestamp())?)
 }
 
-#[derive(serde::Serialize, Clone)]
-pub struct PerformanceData {
-    cpu_usage: f32,
-    memory_usage: f32,
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn list_templates(db: tauri::State<'_, db::Db>) -> Result<Vec<db::Template>, AppError> {
+    let conn = db.0.lock()?;
+    Ok(db::load_templates(&conn)?)
 }
 
 #[tauri::command]
-fn get_performance_data() -> Result<PerformanceData, String> {
-    let mut sys = System::new_all();
-    sys.refresh_cpu();
-    sys.refresh_memory();
+#[tracing::instrument(skip_all, err)]
+fn export_conversation(
+    db: tauri::State<'_, db::Db>,
+    data_dir: tauri::State<'_, DataDir>,
+    conversation_id: String,
+    format: ExportFormat,
+) -> Result<String, AppError> {
+    let conn = db.0.lock()?;
+    let mut messages = db::load_messages(&conn, Some(&conversation_id))?;
+    sidecar::rehydrate_all(&data_dir.0, &mut messages);
+    export::render(&messages, format)
+}
 
-    let cpu_usage = sys.global_cpu_info().cpu_usage();
-    let memory_usage = (sys.used_memory() as f32 / sys.total_memory() as f32) * 100.0;
+/// Restores messages exported as JSON into a new conversation and returns
+/// its id. Messages get new ids; search indexing happens as for any insert.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn import_conversation(db: tauri::State<'_, db::Db>, json: String) -> Result<String, AppError> {
+    let messages = export::parse_messages(&json)?;
+    if messages.is_empty() {
+        return Err(AppError::InvalidParams("no messages to import".into()));
+    }
+    let conversation_id = uuid::Uuid::new_v4().to_string();
+    let conn = db.0.lock()?;
+    db::import_messages(&conn, &conversation_id, messages)?;
+    Ok(conversation_id)
+}
 
-    Ok(PerformanceData {
-        cpu_usage,
-        memory_usage,
-    })
+/// Every conversation with its title and messages in one document, for
+/// backups. Messages are read row by row rather than loaded up front.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn export_all(
+    db: tauri::State<'_, db::Db>,
+    data_dir: tauri::State<'_, DataDir>,
+    format: ExportFormat,
+) -> Result<String, AppError> {
+    let conn = db.0.lock()?;
+    let mut writer = export::BundleWriter::new(format);
+    for conversation in db::load_conversations(&conn)? {
+        writer.start_conversation(&conversation)?;
+        db::for_each_message(&conn, &conversation.id, |message| {
+            if message.content_file.is_none() {
+                return writer.message(message);
+            }
+            let mut message = message.clone();
+            sidecar::rehydrate(&data_dir.0, &mut message);
+            writer.message(&message)
+        })?;
+    }
+    Ok(writer.finish())
+}
+
+/// `export_all` in the background. Returns a task id at once; the document
+/// arrives as the result of `task-complete`.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn start_export(app: tauri::AppHandle, format: ExportFormat) -> Result<String, AppError> {
+    let export_app = app.clone();
+    Ok(tasks::spawn(
+        &app,
+        tasks::TaskKind::Export,
+        |_| async move { export_all(export_app.state(), export_app.state(), format) },
+        |document: &String| format!("exported {} bytes", document.len()),
+    ))
+}
+
+#[derive(serde::Serialize)]
+struct ImportAllResult {
+    imported: usize,
+    /// Conversations left out because their id already exists.
+    skipped: usize,
+}
+
+/// Restores a JSON bundle from `export_all`. Conversations keep their ids,
+/// titles and branch parents; any whose id is already taken are skipped.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn import_all(db: tauri::State<'_, db::Db>, json: String) -> Result<ImportAllResult, AppError> {
+    let conversations = export::parse_bundle(&json)?;
+    let conn = db.0.lock()?;
+    let (imported, skipped) = db::import_conversations(&conn, conversations)?;
+    Ok(ImportAllResult { imported, skipped })
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn search_messages(
+    db: tauri::State<'_, db::Db>,
+    config: tauri::State<'_, ConfigStore>,
+    query: String,
+    limit: u32,
+) -> Result<Vec<ChatMessage>, AppError> {
+    let conn = db.0.lock()?;
+    let mut messages = db::search_messages(&conn, &query, limit)?;
+    timestamps::localize(&mut messages, config.get()?.timestamp_display);
+    Ok(messages)
+}
+
+/// Soft-deletes a message so it can be brought back with `undo_delete`.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn delete_message(db: tauri::State<'_, db::Db>, id: String) -> Result<usize, AppError> {
+    let conn = db.0.lock()?;
+    Ok(db::soft_delete_message(&conn, &id, &now_timestamp())?)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn undo_delete(db: tauri::State<'_, db::Db>, id: String) -> Result<usize, AppError> {
+    let conn = db.0.lock()?;
+    Ok(db::undo_delete(&conn, &id)?)
+}
+
+/// Permanently removes messages deleted more than `older_than_days` ago.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn purge_deleted(db: tauri::State<'_, db::Db>, older_than_days: u32) -> Result<usize, AppError> {
+    let conn = db.0.lock()?;
+    Ok(db::purge_deleted(&conn, days_ago(older_than_days))?)
+}
+
+#[derive(serde::Serialize)]
+struct CompactResult {
+    size_before: u64,
+    size_after: u64,
+}
+
+/// Shrinks the database file after deletes. Refused while a generation is
+/// running, since it holds the database for the whole rebuild.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn compact_database(
+    db: tauri::State<'_, db::Db>,
+    active: tauri::State<'_, ActiveQueries>,
+) -> Result<CompactResult, AppError> {
+    let running = active.count();
+    if running > 0 {
+        return Err(AppError::Busy(format!("{} generation(s) in progress, try again when they finish", running)));
+    }
+    let conn = db.0.lock()?;
+    let size_before = db::file_size(&conn)?;
+    db::compact(&conn)?;
+    let size_after = db::file_size(&conn)?;
+    tracing::info!(size_before, size_after, reclaimed = size_before.saturating_sub(size_after), "database compacted");
+    Ok(CompactResult { size_before, size_after })
+}
+
+/// Epoch millis `days` before now.
+fn days_ago(days: u32) -> i64 {
+    now_timestamp().parse::<i64>().unwrap_or_default() - i64::from(days) * 24 * 60 * 60 * 1000
+}
+
+/// Deletes a conversation's messages (or all of them), keeping pinned ones
+/// unless `force` is set.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn clear_chat_history(
+    db: tauri::State<'_, db::Db>,
+    conversation_id: Option<String>,
+    force: Option<bool>,
+) -> Result<usize, AppError> {
+    let conn = db.0.lock()?;
+    Ok(db::clear_messages(&conn, conversation_id.as_deref(), force.unwrap_or(false))?)
+}
+
+/// Deletes a conversation with its messages, settings, draft and indexes.
+/// Returns how many messages were removed.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn delete_conversation(db: tauri::State<'_, db::Db>, conversation_id: String) -> Result<usize, AppError> {
+    let conn = db.0.lock()?;
+    Ok(db::delete_conversation(&conn, &conversation_id)?)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn pin_message(
+    db: tauri::State<'_, db::Db>,
+    config: tauri::State<'_, ConfigStore>,
+    id: String,
+    pinned: bool,
+) -> Result<ChatMessage, AppError> {
+    let conn = db.0.lock()?;
+    let mut message =
+        db::set_pinned(&conn, &id, pinned)?.ok_or_else(|| AppError::InvalidParams(format!("no message with id {}", id)))?;
+    message.timestamp = timestamps::format_timestamp(&message.timestamp, config.get()?.timestamp_display);
+    Ok(message)
+}
+
+/// Copies through the system clipboard rather than the webview's, which
+/// isn't reliably available.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn copy_to_clipboard(app: tauri::AppHandle, text: String) -> Result<(), AppError> {
+    app.clipboard_manager()
+        .write_text(text)
+        .map_err(|e| AppError::Clipboard(e.to_string()))
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn copy_message(app: tauri::AppHandle, id: String) -> Result<(), AppError> {
+    let message = {
+        let db = app.state::<db::Db>();
+        let conn = db.0.lock()?;
+        db::get_message(&conn, &id)?
+    };
+    let mut message = message.ok_or_else(|| AppError::InvalidParams(format!("no message with id {}", id)))?;
+    sidecar::rehydrate(&app.state::<DataDir>().0, &mut message);
+    copy_to_clipboard(app, message.content)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn get_pinned_messages(
+    db: tauri::State<'_, db::Db>,
+    config: tauri::State<'_, ConfigStore>,
+) -> Result<Vec<ChatMessage>, AppError> {
+    let conn = db.0.lock()?;
+    let mut messages = db::load_pinned_messages(&conn)?;
+    timestamps::localize(&mut messages, config.get()?.timestamp_display);
+    Ok(messages)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn set_model(
+    model: tauri::State<'_, ModelState>,
+    config: tauri::State<'_, ConfigStore>,
+    model_id: String,
+) -> Result<(), AppError> {
+    let model_id = model_id.trim().to_string();
+    if model_id.is_empty() {
+        return Err(AppError::InvalidParams("model id must not be empty".into()));
+    }
+    config.update(|c| c.model_id = model_id.clone())?;
+    *model.0.lock()? = model_id;
+    Ok(())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn get_model(model: tauri::State<'_, ModelState>) -> Result<String, AppError> {
+    generation::current_model(&model)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn count_tokens(text: String, model: String) -> Result<usize, AppError> {
+    Ok(tokens::count_tokens(&text, &model))
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn get_usage_stats(db: tauri::State<'_, db::Db>) -> Result<db::UsageStats, AppError> {
+    let conn = db.0.lock()?;
+    Ok(db::usage_stats(&conn)?)
+}
+
+/// Latency of each successful generation after `since` (ISO 8601), for
+/// charting response times.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn get_latency_history(db: tauri::State<'_, db::Db>, since: String) -> Result<Vec<db::LatencyPoint>, AppError> {
+    let conn = db.0.lock()?;
+    db::latency_history(&conn, &since)?.ok_or_else(|| AppError::InvalidParams(format!("invalid timestamp {:?}", since)))
+}
+
+/// The audit log between two ISO 8601 timestamps, either optional, as CSV.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn export_audit_log(
+    db: tauri::State<'_, db::Db>,
+    since: Option<String>,
+    until: Option<String>,
+) -> Result<String, AppError> {
+    let conn = db.0.lock()?;
+    let entries = db::audit_log(&conn, since.as_deref(), until.as_deref())?
+        .ok_or_else(|| AppError::InvalidParams("invalid timestamp".into()))?;
+    Ok(export::audit_csv(&entries))
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn get_settings(config: tauri::State<'_, ConfigStore>) -> Result<Config, AppError> {
+    config.get()
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn update_settings(
+    app: tauri::AppHandle,
+    config: tauri::State<'_, ConfigStore>,
+    model: tauri::State<'_, ModelState>,
+    http: tauri::State<'_, HttpClient>,
+    cache: tauri::State<'_, ResponseCache>,
+    rate_limiter: tauri::State<'_, RateLimiter>,
+    settings: Config,
+) -> Result<(), AppError> {
+    app.state::<Blocklist>().set(&settings.blocklist)?;
+    http.rebuild(&settings)?;
+    cache.resize(settings.response_cache_size)?;
+    rate_limiter.set_rate(settings.requests_per_minute)?;
+    shortcuts::register(&app, &settings.shortcuts)?;
+    let model_id = settings.model_id.clone();
+    config.update(|c| *c = settings)?;
+    *model.0.lock()? = model_id;
+    Ok(())
 }
 
+/// Switches the canned offline echo on or off; see `Config::offline_mode`.
 #[tauri::command]
-fn get_active_ai_queries() -> Result<u32, String> {
-    // For now, return a dummy value. In a real application, this would query a backend service.
-    Ok(5)
+#[tracing::instrument(skip_all, err)]
+fn set_offline_mode(config: tauri::State<'_, ConfigStore>, enabled: bool) -> Result<(), AppError> {
+    config.update(|c| c.offline_mode = enabled)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn clear_response_cache(cache: tauri::State<'_, ResponseCache>) -> Result<(), AppError> {
+    cache.clear()
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn get_rate_limit_status(rate_limiter: tauri::State<'_, RateLimiter>) -> Result<RateLimitStatus, AppError> {
+    rate_limiter.status()
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+fn get_active_ai_queries(active: tauri::State<'_, ActiveQueries>) -> Result<u32, AppError> {
+    Ok(active.count())
+}
+
+/// Longest the final flush of queued writes may hold up exit.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Stops background tasks and writes out anything still queued.
+fn shutdown(app: &tauri::AppHandle) {
+  let _ = app.state::<PerformanceStream>().stop_all();
+  let _ = shortcuts::unregister_all(app);
+  app.state::<Flusher>().0.abort();
+  if let Err(e) = app.state::<WriteQueue>().shutdown(&app.state::<db::Db>(), SHUTDOWN_TIMEOUT) {
+    tracing::warn!(error = %e, "could not flush queued writes on exit");
+  }
 }
 
 fn main() {
   dotenv::dotenv().ok();
   tauri::Builder::default()
-    .invoke_handler(tauri::generate_handler![get_chat_history, generate_ai_response, get_performance_data, get_active_ai_queries])
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
+    .setup(|app| {
+      app.manage(LaunchTime(std::time::Instant::now()));
+      let data_dir = DataDir::resolve(app.path_resolver().app_data_dir())?.0;
+      app.manage(logging::init(&data_dir));
+      let conn = db::open(&data_dir.join("aios.db"))?;
+      let config = ConfigStore::load(data_dir.join("config.json"));
+      db::purge_deleted(&conn, days_ago(config.get()?.deleted_retention_days))?;
+      app.manage(db::Db(Mutex::new(conn)));
+      app.manage(WriteQueue::default());
+      app.manage(Flusher(writer::spawn_flusher(app.handle())));
+      app.manage(ModelState(Arc::new(Mutex::new(config.get()?.model_id))));
+      app.manage(HttpClient::new(&config.get()?)?);
+      app.manage(ResponseCache::new(config.get()?.response_cache_size));
+      app.manage(Blocklist::new(&config.get()?.blocklist)?);
+      app.manage(RateLimiter::new(config.get()?.requests_per_minute));
+      if let Err(e) = shortcuts::register(&app.handle(), &config.get()?.shortcuts) {
+        tracing::warn!(error = %e, "could not register global shortcuts");
+      }
+      app.manage(config);
+      app.manage(ProviderConfig::from_env());
+      app.manage(InFlightRequests::default());
+      app.manage(Connectivity::default());
+      app.manage(RunningBatches::default());
+      app.manage(ActiveQueries::default());
+      app.manage(GpuMonitor::init());
+      app.manage(PerformanceStream::default());
+      app.manage(NetworkSnapshot::default());
+      app.manage(SharedSystem::new());
+      app.manage(PerformanceHistory::default());
+      app.manage(ModelListCache::default());
+      app.manage(UnmatchedModels::default());
+      app.manage(WarmModels::default());
+      app.manage(Outbox::default());
+      app.manage(DataDir(data_dir));
+      outbox::spawn_auto_flush(app.handle());
+      if let Some(window) = app.get_window("main") {
+        if let Err(e) = window_state::restore(&window, &app.state::<DataDir>().0) {
+          tracing::warn!(error = %e, "could not restore window state");
+        }
+        window.show()?;
+      }
+      Ok(())
+    })
+    .on_window_event(|event| {
+      let window = event.window();
+      match event.event() {
+        tauri::WindowEvent::Destroyed => {
+          let _ = window.state::<PerformanceStream>().stop(window.label());
+        }
+        tauri::WindowEvent::Moved(_) | tauri::WindowEvent::Resized(_) if window.label() == "main" => {
+          if let Err(e) = window_state::save(window) {
+            tracing::warn!(error = %e, "could not save window state");
+          }
+        }
+        _ => {}
+      }
+    })
+    .invoke_handler(tauri::generate_handler![
+      get_chat_history,
+      get_conversations,
+      branch_conversation,
+      set_system_prompt,
+      set_conversation_settings,
+      get_conversation_settings,
+      export_conversation,
+      import_conversation,
+      export_all,
+      start_export,
+      import_all,
+      save_draft,
+      load_draft,
+      save_template,
+      list_templates,
+      search_messages,
+      delete_message,
+      undo_delete,
+      purge_deleted,
+      compact_database,
+      clear_chat_history,
+      delete_conversation,
+      pin_message,
+      get_pinned_messages,
+      copy_to_clipboard,
+      copy_message,
+      generation::generate_ai_response,
+      generation::check_context_fit,
+      generation::generate_json_response,
+      generation::cancel_ai_response,
+      generation::generate_ai_response_stream,
+      generation::regenerate_response,
+      generation::edit_and_resend,
+      generation::summarize_conversation,
+      generation::generate_with_tools,
+      generation::generate_with_context,
+      generation::generate_from_template,
+      generation::generate_batch,
+      generation::start_batch,
+      generation::cancel_batch,
+      health::check_ai_health,
+      health::test_provider,
+      benchmark::benchmark_model,
+      embeddings::generate_embeddings,
+      embeddings::index_conversation,
+      embeddings::semantic_search,
+      images::generate_image,
+      transcription::transcribe_audio,
+      credentials::set_api_token,
+      credentials::clear_api_token,
+      set_model,
+      get_model,
+      models::list_models,
+      warmup::warmup_model,
+      warmup::start_warmup,
+      count_tokens,
+      get_usage_stats,
+      get_latency_history,
+      export_audit_log,
+      get_settings,
+      data_dir::get_data_dir,
+      data_dir::get_data_dir_space,
+      setup::is_configured,
+      outbox::enqueue_prompt,
+      outbox::flush_outbox,
+      outbox::start_outbox_flush,
+      outbox::list_outbox,
+      update_settings,
+      set_offline_mode,
+      shortcuts::set_shortcuts,
+      blocklist::set_blocklist,
+      clear_response_cache,
+      get_rate_limit_status,
+      performance::get_performance_data,
+      performance::start_performance_stream,
+      performance::stop_performance_stream,
+      performance::export_performance_csv,
+      performance::clear_performance_buffer,
+      performance::get_top_processes,
+      performance::get_self_stats,
+      get_active_ai_queries,
+    ])
+    .build(tauri::generate_context!())
+    .expect("error while running tauri application")
+    .run(|app, event| {
+      if let tauri::RunEvent::ExitRequested { .. } | tauri::RunEvent::Exit = event {
+        shutdown(app);
+      }
+    });
 }
@@ -0,0 +1,376 @@
+use rusqlite::{params, Connection, Transaction};
+
+use crate::timestamps;
+
+/// One schema step. Steps are applied in order and each runs at most once per
+/// database, but is written to be safe on a database that already has some of
+/// its tables, since databases created before versioning start at 0.
+type Migration = fn(&Transaction) -> rusqlite::Result<()>;
+
+/// The schema history. A step's version is its position plus one; append new
+/// steps to the end and never reorder or edit shipped ones.
+const MIGRATIONS: &[Migration] = &[messages, conversations, usage, embeddings, drafts_and_templates, summaries, outbox, message_meta, sidecar_content, rfc3339_timestamps];
+
+/// Brings the schema up to date, applying each pending step in its own
+/// transaction together with the bump of `PRAGMA user_version`, so a failed
+/// step leaves the database at the last good version.
+pub fn run(conn: &mut Connection) -> rusqlite::Result<()> {
+    migrate_to(conn, MIGRATIONS.len())
+}
+
+/// The version recorded in the database, 0 for a new or pre-versioning one.
+pub fn current_version(conn: &Connection) -> rusqlite::Result<usize> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get::<_, i64>(0))
+        .map(|version| version as usize)
+}
+
+fn migrate_to(conn: &mut Connection, target: usize) -> rusqlite::Result<()> {
+    let current = current_version(conn)?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().take(target).skip(current) {
+        let tx = conn.transaction()?;
+        migration(&tx)?;
+        tx.pragma_update(None, "user_version", (index + 1) as i64)?;
+        tx.commit()?;
+        tracing::info!(version = index + 1, "applied schema migration");
+    }
+    Ok(())
+}
+
+fn messages(conn: &Transaction) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS messages (
+            id              TEXT PRIMARY KEY,
+            content         TEXT NOT NULL,
+            sender          TEXT NOT NULL,
+            timestamp       TEXT NOT NULL,
+            conversation_id TEXT NOT NULL DEFAULT 'default'
+        );",
+    )?;
+    // Messages saved before conversations existed land in "default".
+    ensure_column(conn, "messages", "conversation_id", "TEXT NOT NULL DEFAULT 'default'")?;
+    ensure_column(conn, "messages", "pinned", "INTEGER NOT NULL DEFAULT 0")?;
+    // Set by `soft_delete_message`; such rows are hidden until purged.
+    ensure_column(conn, "messages", "deleted_at", "TEXT")?;
+    // Replies used to be stored with the sender "ai".
+    conn.execute_batch("UPDATE messages SET sender = 'assistant' WHERE sender = 'ai';")?;
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_messages_conversation ON messages (conversation_id, timestamp);",
+    )?;
+    create_search_index(conn)
+}
+
+fn conversations(conn: &Transaction) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS system_prompts (
+            conversation_id TEXT PRIMARY KEY,
+            prompt          TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS conversation_settings (
+            conversation_id TEXT PRIMARY KEY,
+            model_id        TEXT,
+            params          TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS conversations (
+            id         TEXT PRIMARY KEY,
+            title      TEXT,
+            updated_at TEXT NOT NULL
+        );
+        INSERT OR IGNORE INTO conversations (id, updated_at)
+            SELECT conversation_id, MAX(timestamp) FROM messages GROUP BY conversation_id;",
+    )?;
+    ensure_column(conn, "conversations", "parent_id", "TEXT")
+}
+
+fn usage(conn: &Transaction) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS usage (
+            id                INTEGER PRIMARY KEY,
+            timestamp         TEXT NOT NULL,
+            model             TEXT NOT NULL,
+            prompt_tokens     INTEGER NOT NULL,
+            completion_tokens INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS metrics (
+            id         INTEGER PRIMARY KEY,
+            timestamp  TEXT NOT NULL,
+            model      TEXT NOT NULL,
+            tokens     INTEGER NOT NULL,
+            latency_ms INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS audit_log (
+            id              INTEGER PRIMARY KEY,
+            timestamp       TEXT NOT NULL,
+            model           TEXT NOT NULL,
+            prompt_hash     TEXT NOT NULL,
+            prompt_len      INTEGER NOT NULL,
+            response_len    INTEGER NOT NULL,
+            latency_ms      INTEGER NOT NULL,
+            outcome         TEXT NOT NULL
+        );",
+    )
+}
+
+fn embeddings(conn: &Transaction) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS message_embeddings (
+            message_id TEXT PRIMARY KEY,
+            model      TEXT NOT NULL,
+            vector     BLOB NOT NULL
+        );
+        CREATE TRIGGER IF NOT EXISTS message_embeddings_delete AFTER DELETE ON messages BEGIN
+            DELETE FROM message_embeddings WHERE message_id = old.id;
+        END;
+        CREATE TRIGGER IF NOT EXISTS message_embeddings_soft_delete AFTER UPDATE OF deleted_at ON messages
+            WHEN new.deleted_at IS NOT NULL BEGIN
+            DELETE FROM message_embeddings WHERE message_id = old.id;
+        END;",
+    )
+}
+
+fn drafts_and_templates(conn: &Transaction) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS drafts (
+            conversation_id TEXT PRIMARY KEY,
+            text            TEXT NOT NULL,
+            updated_at      TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS templates (
+            name       TEXT PRIMARY KEY,
+            body       TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );",
+    )
+}
+
+/// Cached conversation summaries, each valid up to the message stamped `up_to`.
+fn summaries(conn: &Transaction) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS conversation_summaries (
+            conversation_id TEXT PRIMARY KEY,
+            up_to           TEXT NOT NULL,
+            summary         TEXT NOT NULL
+        );",
+    )
+}
+
+fn outbox(conn: &Transaction) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS outbox (
+            id              INTEGER PRIMARY KEY,
+            conversation_id TEXT NOT NULL,
+            message         TEXT NOT NULL,
+            created_at      TEXT NOT NULL,
+            attempts        INTEGER NOT NULL DEFAULT 0,
+            last_error      TEXT,
+            failed          INTEGER NOT NULL DEFAULT 0
+        );",
+    )
+}
+
+fn message_meta(conn: &Transaction) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS message_meta (
+            message_id        TEXT PRIMARY KEY,
+            model             TEXT,
+            temperature       REAL,
+            prompt_tokens     INTEGER,
+            completion_tokens INTEGER,
+            latency_ms        INTEGER
+        );
+        CREATE TRIGGER IF NOT EXISTS message_meta_delete AFTER DELETE ON messages BEGIN
+            DELETE FROM message_meta WHERE message_id = old.id;
+        END;",
+    )
+}
+
+/// `content_file` names the sidecar file holding the full text of a message
+/// whose `content` was cut down to a preview.
+fn sidecar_content(conn: &Transaction) -> rusqlite::Result<()> {
+    ensure_column(conn, "messages", "content_file", "TEXT")
+}
+
+/// Message times were epoch milliseconds; they become RFC 3339 in UTC, here
+/// and where they were copied to. Values that aren't plain milliseconds are
+/// left as they are.
+fn rfc3339_timestamps(conn: &Transaction) -> rusqlite::Result<()> {
+    for (table, column) in [("messages", "timestamp"), ("conversations", "updated_at"), ("conversation_summaries", "up_to")] {
+        let rows = {
+            let mut stmt = conn.prepare(&format!("SELECT rowid, {} FROM {} WHERE {} NOT GLOB '*[^0-9]*'", column, table, column))?;
+            let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        let mut update = conn.prepare(&format!("UPDATE {} SET {} = ?2 WHERE rowid = ?1", table, column))?;
+        for (rowid, value) in rows {
+            if let Some(converted) = timestamps::normalize(&value) {
+                update.execute(params![rowid, converted])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Creates the FTS5 index over message content and the triggers that keep it
+/// in step with `messages`. Soft-deleted messages are taken out of the index
+/// and put back if restored. A database that predates the index gets it
+/// backfilled from the existing rows.
+fn create_search_index(conn: &Connection) -> rusqlite::Result<()> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = 'messages_fts')",
+        [],
+        |row| row.get(0),
+    )?;
+    conn.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+            content, content='messages', content_rowid='rowid'
+        );
+        CREATE TRIGGER IF NOT EXISTS messages_fts_insert AFTER INSERT ON messages BEGIN
+            INSERT INTO messages_fts (rowid, content) VALUES (new.rowid, new.content);
+        END;
+        DROP TRIGGER IF EXISTS messages_fts_delete;
+        CREATE TRIGGER messages_fts_delete AFTER DELETE ON messages WHEN old.deleted_at IS NULL BEGIN
+            INSERT INTO messages_fts (messages_fts, rowid, content) VALUES ('delete', old.rowid, old.content);
+        END;
+        DROP TRIGGER IF EXISTS messages_fts_update;
+        CREATE TRIGGER messages_fts_update AFTER UPDATE OF content ON messages WHEN old.deleted_at IS NULL BEGIN
+            INSERT INTO messages_fts (messages_fts, rowid, content) VALUES ('delete', old.rowid, old.content);
+            INSERT INTO messages_fts (rowid, content) VALUES (new.rowid, new.content);
+        END;
+        CREATE TRIGGER IF NOT EXISTS messages_fts_soft_delete AFTER UPDATE OF deleted_at ON messages
+            WHEN old.deleted_at IS NULL AND new.deleted_at IS NOT NULL BEGIN
+            INSERT INTO messages_fts (messages_fts, rowid, content) VALUES ('delete', old.rowid, old.content);
+        END;
+        CREATE TRIGGER IF NOT EXISTS messages_fts_restore AFTER UPDATE OF deleted_at ON messages
+            WHEN old.deleted_at IS NOT NULL AND new.deleted_at IS NULL BEGIN
+            INSERT INTO messages_fts (rowid, content) VALUES (new.rowid, new.content);
+        END;",
+    )?;
+    if !exists {
+        conn.execute_batch("INSERT INTO messages_fts (messages_fts) VALUES ('rebuild');")?;
+    }
+    Ok(())
+}
+
+/// Adds `column` to `table` if a database created by an older build lacks it.
+fn ensure_column(conn: &Connection, table: &str, column: &str, decl: &str) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .iter()
+        .any(|name| name == column);
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tables(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'messages_fts%' ORDER BY name")
+            .unwrap();
+        let names = stmt.query_map([], |row| row.get(0)).unwrap();
+        names.collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    fn migrated_from_scratch() -> Vec<String> {
+        let mut conn = Connection::open_in_memory().unwrap();
+        run(&mut conn).unwrap();
+        tables(&conn)
+    }
+
+    #[test]
+    fn migrates_an_empty_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        run(&mut conn).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), MIGRATIONS.len());
+        for table in ["messages", "conversations", "usage", "message_embeddings", "drafts", "templates"] {
+            assert!(tables(&conn).iter().any(|name| name == table), "missing {}", table);
+        }
+
+        // A second run has nothing left to do.
+        run(&mut conn).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), MIGRATIONS.len());
+    }
+
+    #[test]
+    fn finishes_a_partially_migrated_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate_to(&mut conn, 2).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), 2);
+        assert!(!tables(&conn).iter().any(|name| name == "usage"));
+        conn.execute(
+            "INSERT INTO messages (id, content, sender, timestamp, conversation_id) VALUES ('m1', 'hi', 'user', '1', 'chat')",
+            [],
+        )
+        .unwrap();
+
+        run(&mut conn).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), MIGRATIONS.len());
+        assert_eq!(tables(&conn), migrated_from_scratch());
+        let kept: i64 = conn.query_row("SELECT COUNT(*) FROM messages", [], |row| row.get(0)).unwrap();
+        assert_eq!(kept, 1);
+    }
+
+    #[test]
+    fn adopts_a_database_from_before_versioning() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE messages (id TEXT PRIMARY KEY, content TEXT NOT NULL, sender TEXT NOT NULL, timestamp TEXT NOT NULL);
+            INSERT INTO messages VALUES ('m1', 'hello', 'ai', '1');",
+        )
+        .unwrap();
+
+        run(&mut conn).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), MIGRATIONS.len());
+        let (sender, conversation): (String, String) = conn
+            .query_row("SELECT sender, conversation_id FROM messages", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!((sender.as_str(), conversation.as_str()), ("assistant", "default"));
+        let found: i64 = conn
+            .query_row("SELECT COUNT(*) FROM messages_fts WHERE messages_fts MATCH 'hello'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(found, 1);
+    }
+
+    #[test]
+    fn converts_millis_timestamps_and_keeps_order_with_new_ones() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate_to(&mut conn, MIGRATIONS.len() - 1).unwrap();
+        conn.execute_batch(
+            "INSERT INTO messages (id, content, sender, timestamp, conversation_id) VALUES
+                ('late', 'b', 'user', '100000000000', 'chat'),
+                ('early', 'a', 'user', '99999999999', 'chat'),
+                ('odd', 'c', 'user', 'last tuesday', 'chat');
+            INSERT OR REPLACE INTO conversations (id, updated_at) VALUES ('chat', '100000000000');",
+        )
+        .unwrap();
+
+        run(&mut conn).unwrap();
+        let stamp = |id: &str| -> String {
+            conn.query_row("SELECT timestamp FROM messages WHERE id = ?1", [id], |row| row.get(0)).unwrap()
+        };
+        assert_eq!(stamp("early"), "1973-03-03T09:46:39.999Z");
+        assert_eq!(stamp("late"), "1973-03-03T09:46:40.000Z");
+        assert_eq!(stamp("odd"), "last tuesday");
+        let updated: String = conn.query_row("SELECT updated_at FROM conversations WHERE id = 'chat'", [], |row| row.get(0)).unwrap();
+        assert_eq!(updated, stamp("late"));
+
+        // As strings the old values sorted wrongly once their digit count
+        // changed; converted, they sort in time order, and before anything
+        // stamped from now on.
+        conn.execute(
+            "INSERT INTO messages (id, content, sender, timestamp, conversation_id) VALUES ('new', 'd', 'user', ?1, 'chat')",
+            [timestamps::now()],
+        )
+        .unwrap();
+        let mut stmt = conn
+            .prepare("SELECT id FROM messages WHERE id != 'odd' ORDER BY timestamp, rowid")
+            .unwrap();
+        let order: Vec<String> = stmt.query_map([], |row| row.get(0)).unwrap().collect::<rusqlite::Result<_>>().unwrap();
+        assert_eq!(order, ["early", "late", "new"]);
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tauri::Manager;
+
+use crate::config::{ConfigStore, ModelState};
+use crate::error::AppError;
+use crate::generation::current_model;
+use crate::http::HttpClient;
+use crate::providers::{HuggingFace, OpenAiCompatible, ProviderConfig, ProviderKind};
+
+/// Well-known Hugging Face text-generation models offered alongside the
+/// user's favorites.
+const CURATED_HF_MODELS: &[&str] = &[
+    "gpt2",
+    "mistralai/Mistral-7B-Instruct-v0.2",
+    "HuggingFaceH4/zephyr-7b-beta",
+    "tiiuae/falcon-7b-instruct",
+    "google/flan-t5-large",
+];
+
+/// How long a fetched model list is reused.
+const CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Most close matches offered for an unknown model id.
+const MAX_SUGGESTIONS: usize = 3;
+/// Hub search results ranked for suggestions.
+const SUGGESTION_CANDIDATES: usize = 20;
+/// How long an id with no close matches is left alone before searching again.
+const NO_MATCH_TTL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct ModelInfo {
+    id: String,
+    /// The id without its organisation prefix.
+    name: String,
+    selected: bool,
+}
+
+/// The last list fetched from a server, so repeated calls don't hit it.
+#[derive(Default)]
+pub struct ModelListCache(Mutex<Option<(Instant, ProviderKind, Vec<String>)>>);
+
+async fn model_ids(app: &tauri::AppHandle, kind: ProviderKind) -> Result<Vec<String>, AppError> {
+    let provider_config = app.state::<ProviderConfig>();
+    match kind {
+        ProviderKind::HuggingFace => {
+            let mut ids: Vec<String> = CURATED_HF_MODELS.iter().map(|id| id.to_string()).collect();
+            for favorite in app.state::<ConfigStore>().get()?.favorite_models {
+                if !ids.contains(&favorite) {
+                    ids.push(favorite);
+                }
+            }
+            Ok(ids)
+        }
+        ProviderKind::Ollama => Ok(vec![provider_config.ollama_model.clone()]),
+        ProviderKind::OpenAi => {
+            let cache = app.state::<ModelListCache>();
+            if let Some((fetched, cached_kind, ids)) = &*cache.0.lock()? {
+                if *cached_kind == kind && fetched.elapsed() < CACHE_TTL {
+                    return Ok(ids.clone());
+                }
+            }
+            let http = app.state::<HttpClient>();
+            let provider = OpenAiCompatible::new(
+                http.get()?,
+                &provider_config.openai_url,
+                provider_config.openai_api_key.as_deref(),
+                &provider_config.openai_model,
+            );
+            let ids = provider.list_models().await.map_err(|e| http.explain(e.into()))?;
+            *cache.0.lock()? = Some((Instant::now(), kind, ids.clone()));
+            Ok(ids)
+        }
+    }
+}
+
+/// Model ids whose suggestion search found nothing, and when.
+#[derive(Default)]
+pub struct UnmatchedModels(Mutex<HashMap<String, Instant>>);
+
+/// Adds close matches from the Hub to a 404 for the Hugging Face model
+/// `model`, e.g. "did you mean `gpt2-large`?". The lookup is best-effort:
+/// any other error, or a failed search, comes back unchanged.
+pub async fn explain_not_found(app: &tauri::AppHandle, model: &str, err: AppError) -> AppError {
+    let AppError::ApiStatus { code: 404, body, request_id } = err else {
+        return err;
+    };
+    let body = match suggest(app, model).await {
+        Ok(suggestions) if !suggestions.is_empty() => {
+            let quoted: Vec<String> = suggestions.iter().map(|id| format!("`{}`", id)).collect();
+            format!("{} (did you mean {}?)", body, quoted.join(", "))
+        }
+        Ok(_) => body,
+        Err(e) => {
+            tracing::debug!(model, error = %e, "model suggestion lookup failed");
+            body
+        }
+    };
+    AppError::ApiStatus { code: 404, body, request_id }
+}
+
+/// Up to `MAX_SUGGESTIONS` Hub model ids closest to `model`. The search uses
+/// the first half of the name, so a typo further on still finds the model.
+async fn suggest(app: &tauri::AppHandle, model: &str) -> Result<Vec<String>, AppError> {
+    let unmatched = app.state::<UnmatchedModels>();
+    if unmatched.0.lock()?.get(model).is_some_and(|at| at.elapsed() < NO_MATCH_TTL) {
+        return Ok(Vec::new());
+    }
+    let name = model.rsplit('/').next().unwrap_or(model);
+    let query: String = name.chars().take(((name.chars().count() + 1) / 2).max(3)).collect();
+    let candidates = HuggingFace::search_models(&app.state::<HttpClient>().get()?, &query, SUGGESTION_CANDIDATES).await?;
+
+    let wanted = model.to_lowercase();
+    let max_distance = (wanted.chars().count() / 3).max(2);
+    let mut ranked: Vec<(usize, String)> = candidates
+        .into_iter()
+        .filter(|id| *id != model)
+        .map(|id| {
+            // Ids without an organisation are compared by name alone.
+            let distance = if model.contains('/') {
+                edit_distance(&wanted, &id.to_lowercase())
+            } else {
+                edit_distance(&wanted, &id.rsplit('/').next().unwrap_or(&id).to_lowercase())
+            };
+            (distance, id)
+        })
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    ranked.sort();
+    let suggestions: Vec<String> = ranked.into_iter().take(MAX_SUGGESTIONS).map(|(_, id)| id).collect();
+    if suggestions.is_empty() {
+        unmatched.0.lock()?.insert(model.to_string(), Instant::now());
+    }
+    Ok(suggestions)
+}
+
+/// Levenshtein distance over characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb { diagonal } else { 1 + diagonal.min(above).min(row[j]) };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// Models available from `provider` (the default one if not given), with
+/// the one currently in use marked `selected`.
+#[tauri::command]
+#[tracing::instrument(skip(app), err)]
+pub async fn list_models(app: tauri::AppHandle, provider: Option<ProviderKind>) -> Result<Vec<ModelInfo>, AppError> {
+    let provider_config = app.state::<ProviderConfig>();
+    let kind = provider.unwrap_or(provider_config.default_kind);
+    let selected = match kind {
+        ProviderKind::HuggingFace => current_model(&app.state::<ModelState>())?,
+        ProviderKind::Ollama => provider_config.ollama_model.clone(),
+        ProviderKind::OpenAi => provider_config.openai_model.clone(),
+    };
+    Ok(model_ids(&app, kind)
+        .await?
+        .into_iter()
+        .map(|id| ModelInfo {
+            name: id.rsplit('/').next().unwrap_or(&id).to_string(),
+            selected: id == selected,
+            id,
+        })
+        .collect())
+}
@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use tauri::Manager;
+
+use crate::blocklist::Blocklist;
+use crate::config::{ConfigStore, ModelState};
+use crate::connectivity::Connectivity;
+use crate::db;
+use crate::error::AppError;
+use crate::generation;
+use crate::http::HttpClient;
+use crate::now_timestamp;
+use crate::providers::{self, ProviderConfig};
+use crate::tasks::{self, TaskKind};
+
+/// Sends an entry gets before it is marked failed and left alone.
+const MAX_ATTEMPTS: u32 = 5;
+/// How often the background task looks for queued prompts to send.
+const AUTO_FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Serializes flushes, so the background task and `flush_outbox` never send
+/// the same entry twice.
+#[derive(Default)]
+pub struct Outbox(tokio::sync::Mutex<()>);
+
+#[derive(serde::Serialize, Debug, Default)]
+pub struct OutboxFlush {
+    pub sent: usize,
+    /// Sends that failed this time, including entries that just hit
+    /// `MAX_ATTEMPTS`.
+    pub failed: usize,
+    /// Entries still queued for a later flush.
+    pub remaining: usize,
+}
+
+/// Sends queued prompts oldest first. A failure leaves its entry queued
+/// with one more attempt counted and holds back the rest of that
+/// conversation, so replies stay in order. Being offline stops the flush
+/// without counting against anything.
+pub async fn flush(window: &tauri::Window) -> Result<OutboxFlush, AppError> {
+    let outbox = window.state::<Outbox>();
+    let _flushing = outbox.0.lock().await;
+    let entries = {
+        let db = window.state::<db::Db>();
+        let conn = db.0.lock()?;
+        db::load_outbox(&conn, true)?
+    };
+
+    let mut report = OutboxFlush::default();
+    let mut held_back = HashSet::new();
+    for entry in &entries {
+        if held_back.contains(&entry.conversation_id) {
+            continue;
+        }
+        let result = generation::send_queued(window, entry.conversation_id.clone(), entry.message.clone()).await;
+        let db = window.state::<db::Db>();
+        let conn = db.0.lock()?;
+        match result {
+            Ok(_) => {
+                db::remove_outbox(&conn, entry.id)?;
+                report.sent += 1;
+            }
+            Err(AppError::Offline(origin)) => {
+                tracing::info!(%origin, "still offline, outbox flush stopped");
+                break;
+            }
+            Err(e) => {
+                let gave_up = db::record_outbox_failure(&conn, entry.id, &e.to_string(), MAX_ATTEMPTS)?;
+                if gave_up {
+                    tracing::warn!(id = entry.id, error = %e, "queued prompt failed for good");
+                }
+                report.failed += 1;
+                held_back.insert(entry.conversation_id.clone());
+            }
+        }
+    }
+    let db = window.state::<db::Db>();
+    let conn = db.0.lock()?;
+    report.remaining = db::load_outbox(&conn, true)?.len();
+    Ok(report)
+}
+
+/// Every `AUTO_FLUSH_INTERVAL`, flushes the outbox through the main window
+/// if anything is queued and the default provider is reachable.
+pub fn spawn_auto_flush(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(AUTO_FLUSH_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = auto_flush(&app).await {
+                tracing::debug!(error = %e, "outbox auto-flush skipped");
+            }
+        }
+    });
+}
+
+async fn auto_flush(app: &tauri::AppHandle) -> Result<(), AppError> {
+    let pending = {
+        let db = app.state::<db::Db>();
+        let conn = db.0.lock()?;
+        !db::load_outbox(&conn, true)?.is_empty()
+    };
+    if !pending || app.state::<ConfigStore>().get()?.offline_mode {
+        return Ok(());
+    }
+    let Some(window) = app.get_window("main") else {
+        return Ok(());
+    };
+    let provider_config = app.state::<ProviderConfig>();
+    let client = app.state::<HttpClient>().get()?;
+    let model = generation::current_model(&app.state::<ModelState>())?;
+    let provider = providers::build(provider_config.default_kind, &provider_config, client.clone(), &model)?;
+    app.state::<Connectivity>().ensure_reachable(&client, provider.endpoint()).await?;
+    let report = flush(&window).await;
+    if let Ok(report) = &report {
+        tracing::info!(sent = report.sent, failed = report.failed, remaining = report.remaining, "outbox flushed");
+        if report.sent + report.failed == 0 {
+            return Ok(());
+        }
+    }
+    let task_id = uuid::Uuid::new_v4().to_string();
+    tasks::notify(app, task_id, TaskKind::OutboxFlush, report.as_ref().map(|report| (summary(report), report)));
+    report.map(drop)
+}
+
+fn summary(report: &OutboxFlush) -> String {
+    format!("{} sent, {} failed, {} still queued", report.sent, report.failed, report.remaining)
+}
+
+/// Queues `message` to be sent to `conversation_id` later, e.g. while
+/// offline. Returns the entry's id.
+#[tauri::command]
+#[tracing::instrument(skip(db, blocklist, message), err)]
+pub fn enqueue_prompt(
+    db: tauri::State<'_, db::Db>,
+    blocklist: tauri::State<'_, Blocklist>,
+    conversation_id: String,
+    message: String,
+) -> Result<i64, AppError> {
+    if message.trim().is_empty() {
+        return Err(AppError::InvalidParams("message must not be empty".into()));
+    }
+    blocklist.check(&message)?;
+    let conn = db.0.lock()?;
+    Ok(db::enqueue_outbox(&conn, &conversation_id, &message, &now_timestamp())?)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn flush_outbox(window: tauri::Window) -> Result<OutboxFlush, AppError> {
+    flush(&window).await
+}
+
+/// `flush_outbox` in the background. Returns a task id at once; the report
+/// arrives with `task-complete`.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub fn start_outbox_flush(window: tauri::Window) -> Result<String, AppError> {
+    let app = window.app_handle();
+    Ok(tasks::spawn(&app, TaskKind::OutboxFlush, |_| async move { flush(&window).await }, summary))
+}
+
+/// Everything in the outbox, including entries marked failed.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub fn list_outbox(db: tauri::State<'_, db::Db>) -> Result<Vec<db::OutboxEntry>, AppError> {
+    let conn = db.0.lock()?;
+    Ok(db::load_outbox(&conn, false)?)
+}
@@ -0,0 +1,485 @@
+use sysinfo::{ComponentExt, CpuExt, DiskExt, NetworkExt, NetworksExt, PidExt, ProcessExt, System, SystemExt};
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use nvml_wrapper::Nvml;
+use tauri::async_runtime::JoinHandle;
+use tauri::Manager;
+
+use crate::battery::{self, BatteryInfo};
+use crate::error::AppError;
+use crate::now_timestamp;
+
+/// NVML handle, initialized once at startup. `None` when there's no NVIDIA
+/// GPU or the driver library can't be loaded.
+pub struct GpuMonitor(Option<Nvml>);
+
+impl GpuMonitor {
+    pub fn init() -> Self {
+        GpuMonitor(Nvml::init().ok())
+    }
+
+    /// Utilization percentage and used memory (MiB) of the first GPU.
+    fn sample(&self) -> (Option<f32>, Option<u64>) {
+        let Some(device) = self.0.as_ref().and_then(|nvml| nvml.device_by_index(0).ok()) else {
+            return (None, None);
+        };
+        let usage = device.utilization_rates().ok().map(|u| u.gpu as f32);
+        let memory_used_mb = device.memory_info().ok().map(|m| m.used / (1024 * 1024));
+        (usage, memory_used_mb)
+    }
+}
+
+/// A snapshot of machine load for the dashboard.
+///
+/// Reading it takes about `SystemExt::MINIMUM_CPU_UPDATE_INTERVAL` (~200ms):
+/// sysinfo computes CPU usage from the difference between two samples, so
+/// the CPU is refreshed twice with that delay in between. Streams skip the
+/// wait by measuring against their previous reading.
+#[derive(serde::Serialize, Clone)]
+pub struct PerformanceData {
+    /// Average of the last `CPU_SMOOTHING_SAMPLES` readings, which evens out
+    /// the jumps between single readings. Until that many readings have been
+    /// taken it averages fewer, so the first values are less steady.
+    cpu_usage: f32,
+    /// This reading alone.
+    cpu_usage_raw: f32,
+    /// Used memory as a percentage of total, computed from the byte counts.
+    memory_usage: f32,
+    total_memory_bytes: u64,
+    used_memory_bytes: u64,
+    /// Memory that can be handed out without swapping, including reclaimable
+    /// cache, so it is usually more than `total - used`.
+    available_memory_bytes: u64,
+    total_swap_bytes: u64,
+    used_swap_bytes: u64,
+    /// Usage of each logical core, in the order sysinfo reports them.
+    per_core: Vec<f32>,
+    /// `None` on machines without an NVIDIA GPU.
+    gpu_usage: Option<f32>,
+    gpu_memory_used_mb: Option<u64>,
+    /// Totals across all mounted disks.
+    disk_used_bytes: u64,
+    disk_total_bytes: u64,
+    /// Throughput across all interfaces since the previous reading. Both are
+    /// zero on the first reading, which has nothing to compare against.
+    rx_bytes_per_sec: f64,
+    tx_bytes_per_sec: f64,
+    /// Temperature of the CPU package sensor; `None` where the platform
+    /// exposes no sensors.
+    cpu_temp_celsius: Option<f32>,
+    /// Every sensor sysinfo can read, for a detailed view.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    components: Vec<ComponentTemp>,
+    /// Only read when asked for; `None` as well on machines without a battery.
+    battery: Option<BatteryInfo>,
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct ComponentTemp {
+    label: String,
+    temperature_celsius: f32,
+    critical_celsius: Option<f32>,
+}
+
+/// Cumulative network counters from the previous reading, used to turn the
+/// totals sysinfo reports into per-second rates.
+#[derive(Default)]
+pub struct NetworkSnapshot(Mutex<Option<(Instant, u64, u64)>>);
+
+impl NetworkSnapshot {
+    /// Records the current totals and returns the rx/tx rates since the last
+    /// call.
+    fn rates(&self, rx_total: u64, tx_total: u64) -> Result<(f64, f64), AppError> {
+        let now = Instant::now();
+        let mut previous = self.0.lock()?;
+        let rates = match *previous {
+            Some((at, rx, tx)) => {
+                let secs = now.duration_since(at).as_secs_f64();
+                if secs > 0.0 {
+                    (rx_total.saturating_sub(rx) as f64 / secs, tx_total.saturating_sub(tx) as f64 / secs)
+                } else {
+                    (0.0, 0.0)
+                }
+            }
+            None => (0.0, 0.0),
+        };
+        *previous = Some((now, rx_total, tx_total));
+        Ok(rates)
+    }
+}
+
+/// Most samples `PerformanceHistory` keeps; at one per second that's an hour.
+const HISTORY_LEN: usize = 3600;
+
+/// Samples collected by `start_performance_stream`, with the time each was
+/// taken, oldest first. Older samples are dropped past `HISTORY_LEN`.
+#[derive(Default)]
+pub struct PerformanceHistory(Mutex<VecDeque<(String, PerformanceData)>>);
+
+impl PerformanceHistory {
+    fn push(&self, data: PerformanceData) -> Result<(), AppError> {
+        let mut samples = self.0.lock()?;
+        if samples.len() == HISTORY_LEN {
+            samples.pop_front();
+        }
+        samples.push_back((now_timestamp(), data));
+        Ok(())
+    }
+}
+
+/// The background tasks behind `start_performance_stream`, keyed by the
+/// label of the window each one emits to.
+#[derive(Default)]
+pub struct PerformanceStream(Mutex<HashMap<String, JoinHandle<()>>>);
+
+impl PerformanceStream {
+    /// Stops the stream feeding `label`, if any. Called when a window is
+    /// destroyed so its task doesn't outlive it.
+    pub fn stop(&self, label: &str) -> Result<(), AppError> {
+        if let Some(task) = self.0.lock()?.remove(label) {
+            task.abort();
+        }
+        Ok(())
+    }
+
+    pub fn stop_all(&self) -> Result<(), AppError> {
+        for (_, task) in self.0.lock()?.drain() {
+            task.abort();
+        }
+        Ok(())
+    }
+}
+
+/// CPU usage read from one refresh is the average since the previous one,
+/// which is only meaningful if that was recent.
+const MAX_CPU_SAMPLE_AGE: Duration = Duration::from_secs(2);
+
+/// How many recent CPU readings `PerformanceData::cpu_usage` averages.
+const CPU_SMOOTHING_SAMPLES: usize = 5;
+
+struct Sampler {
+    sys: System,
+    last_cpu_refresh: Option<Instant>,
+    /// The latest CPU readings, oldest first.
+    cpu_readings: VecDeque<f32>,
+}
+
+impl Sampler {
+    /// Adds a reading and returns the average over the window.
+    fn smooth_cpu(&mut self, reading: f32) -> f32 {
+        if self.cpu_readings.len() == CPU_SMOOTHING_SAMPLES {
+            self.cpu_readings.pop_front();
+        }
+        self.cpu_readings.push_back(reading);
+        self.cpu_readings.iter().sum::<f32>() / self.cpu_readings.len() as f32
+    }
+}
+
+/// One `System` shared by every sampler, refreshed in place. Building a
+/// fresh `System::new_all()` per reading enumerated every process, disk and
+/// sensor and cost ~4ms even in a near-empty container (far more on a busy
+/// desktop); the targeted refreshes cost ~0.1ms there. Samplers take turns
+/// on the async lock, which can't be poisoned. It also holds the readings
+/// CPU usage is smoothed over.
+pub struct SharedSystem(tokio::sync::Mutex<Sampler>);
+
+impl SharedSystem {
+    pub fn new() -> Self {
+        let mut sys = System::new();
+        sys.refresh_disks_list();
+        sys.refresh_networks_list();
+        sys.refresh_components_list();
+        SharedSystem(tokio::sync::Mutex::new(Sampler {
+            sys,
+            last_cpu_refresh: None,
+            cpu_readings: VecDeque::with_capacity(CPU_SMOOTHING_SAMPLES),
+        }))
+    }
+}
+
+async fn sample(
+    system: &SharedSystem,
+    gpu: &GpuMonitor,
+    network: &NetworkSnapshot,
+    include_battery: bool,
+) -> Result<PerformanceData, AppError> {
+    let mut sampler = system.0.lock().await;
+    let since_last = sampler.last_cpu_refresh.map(|at| at.elapsed());
+    let sys = &mut sampler.sys;
+    // A stream refreshes often enough that the previous refresh serves as the
+    // baseline; otherwise take two readings the minimum interval apart.
+    if !since_last.is_some_and(|age| (System::MINIMUM_CPU_UPDATE_INTERVAL..=MAX_CPU_SAMPLE_AGE).contains(&age)) {
+        sys.refresh_cpu();
+        tokio::time::sleep(System::MINIMUM_CPU_UPDATE_INTERVAL).await;
+    }
+    sys.refresh_cpu();
+    sys.refresh_memory();
+    sys.refresh_disks();
+    sys.refresh_networks();
+    sys.refresh_components();
+    sampler.last_cpu_refresh = Some(Instant::now());
+
+    let cpu_usage_raw = sampler.sys.global_cpu_info().cpu_usage();
+    let cpu_usage = sampler.smooth_cpu(cpu_usage_raw);
+    let sys = &sampler.sys;
+    let total_memory_bytes = sys.total_memory();
+    let used_memory_bytes = sys.used_memory();
+    let memory_usage = if total_memory_bytes == 0 {
+        0.0
+    } else {
+        (used_memory_bytes as f64 / total_memory_bytes as f64 * 100.0) as f32
+    };
+    let per_core = sys.cpus().iter().map(|c| c.cpu_usage()).collect();
+    let (gpu_usage, gpu_memory_used_mb) = gpu.sample();
+
+    let disk_total_bytes: u64 = sys.disks().iter().map(|d| d.total_space()).sum();
+    let disk_available_bytes: u64 = sys.disks().iter().map(|d| d.available_space()).sum();
+    let rx_total = sys.networks().iter().map(|(_, n)| n.total_received()).sum();
+    let tx_total = sys.networks().iter().map(|(_, n)| n.total_transmitted()).sum();
+    let (rx_bytes_per_sec, tx_bytes_per_sec) = network.rates(rx_total, tx_total)?;
+
+    let cpu_temp_celsius = sys
+        .components()
+        .iter()
+        .find(|c| c.label().contains("CPU") || c.label().contains("Package"))
+        .map(|c| c.temperature());
+    let components = sys
+        .components()
+        .iter()
+        .map(|c| ComponentTemp {
+            label: c.label().to_string(),
+            temperature_celsius: c.temperature(),
+            critical_celsius: c.critical(),
+        })
+        .collect();
+
+    Ok(PerformanceData {
+        cpu_usage,
+        cpu_usage_raw,
+        memory_usage,
+        total_memory_bytes,
+        used_memory_bytes,
+        available_memory_bytes: sys.available_memory(),
+        total_swap_bytes: sys.total_swap(),
+        used_swap_bytes: sys.used_swap(),
+        per_core,
+        gpu_usage,
+        gpu_memory_used_mb,
+        disk_used_bytes: disk_total_bytes.saturating_sub(disk_available_bytes),
+        disk_total_bytes,
+        rx_bytes_per_sec,
+        tx_bytes_per_sec,
+        cpu_temp_celsius,
+        components,
+        battery: if include_battery { battery::read() } else { None },
+    })
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn get_performance_data(
+    system: tauri::State<'_, SharedSystem>,
+    gpu: tauri::State<'_, GpuMonitor>,
+    network: tauri::State<'_, NetworkSnapshot>,
+    include_battery: Option<bool>,
+) -> Result<PerformanceData, AppError> {
+    sample(&system, &gpu, &network, include_battery.unwrap_or(false)).await
+}
+
+/// Emits a `performance-update` event with fresh `PerformanceData` to the
+/// calling window every `interval_ms`, also keeping each sample in
+/// `PerformanceHistory` for `export_performance_csv`. Starting a new stream replaces that
+/// window's previous one; other windows' streams are left alone. Ticks that
+/// fall behind (sampling itself takes ~200ms) are skipped rather than queued.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub fn start_performance_stream(
+    window: tauri::Window,
+    stream: tauri::State<'_, PerformanceStream>,
+    interval_ms: u64,
+    include_battery: Option<bool>,
+) -> Result<(), AppError> {
+    let include_battery = include_battery.unwrap_or(false);
+    let period = Duration::from_millis(interval_ms).max(System::MINIMUM_CPU_UPDATE_INTERVAL);
+    let label = window.label().to_string();
+    let task = tauri::async_runtime::spawn(async move {
+        let system = window.state::<SharedSystem>();
+        let gpu = window.state::<GpuMonitor>();
+        let network = window.state::<NetworkSnapshot>();
+        let history = window.state::<PerformanceHistory>();
+        let mut ticker = tokio::time::interval(period);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            ticker.tick().await;
+            let Ok(data) = sample(&system, &gpu, &network, include_battery).await else {
+                break;
+            };
+            if history.push(data.clone()).is_err() {
+                break;
+            }
+            if window.emit("performance-update", data).is_err() {
+                break;
+            }
+        }
+    });
+    if let Some(previous) = stream.0.lock()?.insert(label, task) {
+        previous.abort();
+    }
+    Ok(())
+}
+
+/// The collected samples as CSV, one row per sample with the timestamp
+/// first. Missing readings (no GPU, sensor or battery) are left empty.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub fn export_performance_csv(history: tauri::State<'_, PerformanceHistory>) -> Result<String, AppError> {
+    let mut csv = String::from(
+        "timestamp,cpu_usage,memory_usage,used_memory_bytes,total_memory_bytes,available_memory_bytes,\
+         used_swap_bytes,total_swap_bytes,gpu_usage,gpu_memory_used_mb,disk_used_bytes,disk_total_bytes,\
+         rx_bytes_per_sec,tx_bytes_per_sec,cpu_temp_celsius,battery_percentage\n",
+    );
+    for (timestamp, d) in history.0.lock()?.iter() {
+        let row = [
+            timestamp.clone(),
+            d.cpu_usage.to_string(),
+            d.memory_usage.to_string(),
+            d.used_memory_bytes.to_string(),
+            d.total_memory_bytes.to_string(),
+            d.available_memory_bytes.to_string(),
+            d.used_swap_bytes.to_string(),
+            d.total_swap_bytes.to_string(),
+            optional(d.gpu_usage),
+            optional(d.gpu_memory_used_mb),
+            d.disk_used_bytes.to_string(),
+            d.disk_total_bytes.to_string(),
+            d.rx_bytes_per_sec.to_string(),
+            d.tx_bytes_per_sec.to_string(),
+            optional(d.cpu_temp_celsius),
+            optional(d.battery.as_ref().map(|b| b.percentage)),
+        ];
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    Ok(csv)
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub fn clear_performance_buffer(history: tauri::State<'_, PerformanceHistory>) -> Result<(), AppError> {
+    history.0.lock()?.clear();
+    Ok(())
+}
+
+/// Stops the calling window's stream.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub fn stop_performance_stream(window: tauri::Window, stream: tauri::State<'_, PerformanceStream>) -> Result<(), AppError> {
+    stream.stop(window.label())
+}
+
+#[derive(serde::Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    Cpu,
+    Memory,
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct ProcessInfo {
+    pid: u32,
+    name: String,
+    cpu_usage: f32,
+    memory_bytes: u64,
+}
+
+/// The `limit` processes using the most CPU or memory, highest first. Like
+/// `get_performance_data`, this samples twice to get meaningful CPU figures.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn get_top_processes(sort_by: SortKey, limit: usize) -> Result<Vec<ProcessInfo>, AppError> {
+    let mut sys = System::new();
+    sys.refresh_processes();
+    tokio::time::sleep(System::MINIMUM_CPU_UPDATE_INTERVAL).await;
+    sys.refresh_processes();
+
+    let mut processes: Vec<ProcessInfo> = sys
+        .processes()
+        .iter()
+        .map(|(pid, process)| ProcessInfo {
+            pid: pid.as_u32(),
+            name: process.name().to_string(),
+            cpu_usage: process.cpu_usage(),
+            memory_bytes: process.memory(),
+        })
+        .collect();
+    match sort_by {
+        SortKey::Cpu => processes.sort_by(|a, b| b.cpu_usage.total_cmp(&a.cpu_usage)),
+        SortKey::Memory => processes.sort_by(|a, b| b.memory_bytes.cmp(&a.memory_bytes)),
+    }
+    processes.truncate(limit);
+    Ok(processes)
+}
+
+/// When the app started, for `get_self_stats`.
+pub struct LaunchTime(pub Instant);
+
+#[derive(serde::Serialize, Clone)]
+pub struct SelfStats {
+    /// Percent of one core, so it can exceed 100 on multi-core machines.
+    cpu_usage: f32,
+    memory_bytes: u64,
+    virtual_memory_bytes: u64,
+    /// `None` where sysinfo doesn't list threads (everywhere but Linux).
+    thread_count: Option<usize>,
+    uptime_secs: u64,
+}
+
+/// The app's own resource use, for spotting leaks in long sessions. Samples
+/// twice like `get_top_processes`, but only this process.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn get_self_stats(launched: tauri::State<'_, LaunchTime>) -> Result<SelfStats, AppError> {
+    let pid = sysinfo::get_current_pid().map_err(|e| std::io::Error::new(std::io::ErrorKind::Unsupported, e))?;
+    let mut sys = System::new();
+    sys.refresh_process(pid);
+    tokio::time::sleep(System::MINIMUM_CPU_UPDATE_INTERVAL).await;
+    sys.refresh_process(pid);
+    let process = sys
+        .process(pid)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "own process not listed"))?;
+
+    #[cfg(target_os = "linux")]
+    let thread_count = Some(process.tasks.len().max(1));
+    #[cfg(not(target_os = "linux"))]
+    let thread_count = None;
+
+    Ok(SelfStats {
+        cpu_usage: process.cpu_usage(),
+        memory_bytes: process.memory(),
+        virtual_memory_bytes: process.virtual_memory(),
+        thread_count,
+        uptime_secs: launched.0.elapsed().as_secs(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn samples_without_a_token() {
+        std::env::remove_var("HF_TOKEN");
+        let data = sample(&SharedSystem::new(), &GpuMonitor::init(), &NetworkSnapshot::default(), false)
+            .await
+            .unwrap();
+        assert!(data.total_memory_bytes > 0);
+        assert!(data.battery.is_none());
+    }
+}
@@ -0,0 +1,113 @@
+/// One step of the cleanup applied to replies before they are returned and
+/// stored, configured as an ordered list in `post_processors`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PostProcessor {
+    /// Drops a copy of the prompt from the start of the reply, as some
+    /// Hugging Face models send back their input ahead of the completion.
+    StripPromptEcho,
+    Trim,
+    /// Turns each run of whitespace into one space, or one newline if the
+    /// run contains a line break.
+    CollapseWhitespace,
+    /// Cuts the reply at the first occurrence of the string.
+    StopAt(String),
+}
+
+impl PostProcessor {
+    fn apply(&self, prompt: &str, text: String) -> String {
+        match self {
+            PostProcessor::StripPromptEcho => strip_prompt_echo(prompt, text),
+            PostProcessor::Trim => text.trim().to_string(),
+            PostProcessor::CollapseWhitespace => collapse_whitespace(&text),
+            PostProcessor::StopAt(stop) if !stop.is_empty() => match text.find(stop.as_str()) {
+                Some(end) => text[..end].to_string(),
+                None => text,
+            },
+            PostProcessor::StopAt(_) => text,
+        }
+    }
+}
+
+/// Runs `text` through `chain` in order. `prompt` is what the model was
+/// given, for `StripPromptEcho`.
+pub fn apply(chain: &[PostProcessor], prompt: &str, text: String) -> String {
+    chain.iter().fold(text, |text, processor| processor.apply(prompt, text))
+}
+
+/// Leading whitespace on either side is ignored when matching, since models
+/// often add or drop some around the echo.
+fn strip_prompt_echo(prompt: &str, text: String) -> String {
+    let prompt = prompt.trim();
+    if prompt.is_empty() {
+        return text;
+    }
+    match text.trim_start().strip_prefix(prompt) {
+        Some(rest) => rest.to_string(),
+        None => text,
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut run: Option<char> = None;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if c == '\n' || run.is_none() {
+                run = Some(if c == '\n' { '\n' } else { ' ' });
+            }
+            continue;
+        }
+        if let Some(space) = run.take() {
+            out.push(space);
+        }
+        out.push(c);
+    }
+    out.extend(run);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(processor: PostProcessor, prompt: &str, text: &str) -> String {
+        apply(&[processor], prompt, text.to_string())
+    }
+
+    #[test]
+    fn strip_prompt_echo_removes_a_leading_copy_only() {
+        let prompt = "User: hi\nAssistant:";
+        assert_eq!(run(PostProcessor::StripPromptEcho, prompt, "User: hi\nAssistant: hello"), " hello");
+        assert_eq!(run(PostProcessor::StripPromptEcho, prompt, "\n User: hi\nAssistant:hello"), "hello");
+        assert_eq!(run(PostProcessor::StripPromptEcho, prompt, "hello User: hi\nAssistant:"), "hello User: hi\nAssistant:");
+        assert_eq!(run(PostProcessor::StripPromptEcho, "", "hello"), "hello");
+    }
+
+    #[test]
+    fn trim_removes_surrounding_whitespace() {
+        assert_eq!(run(PostProcessor::Trim, "", "\n  hello there \t\n"), "hello there");
+    }
+
+    #[test]
+    fn collapse_whitespace_keeps_line_breaks() {
+        assert_eq!(run(PostProcessor::CollapseWhitespace, "", "a  b\t\tc"), "a b c");
+        assert_eq!(run(PostProcessor::CollapseWhitespace, "", "a \n\n  b "), "a\nb ");
+    }
+
+    #[test]
+    fn stop_at_cuts_at_the_first_match() {
+        assert_eq!(run(PostProcessor::StopAt("###".into()), "", "answer### more ### rest"), "answer");
+        assert_eq!(run(PostProcessor::StopAt("###".into()), "", "no marker"), "no marker");
+        assert_eq!(run(PostProcessor::StopAt(String::new()), "", "unchanged"), "unchanged");
+    }
+
+    #[test]
+    fn chain_runs_in_order_and_defaults_to_empty() {
+        let chain = [PostProcessor::StopAt("User:".into()), PostProcessor::Trim];
+        assert_eq!(apply(&chain, "", " reply \nUser: next".into()), "reply");
+        assert_eq!(apply(&[], "", " as is ".into()), " as is ");
+        let parsed: Vec<PostProcessor> = serde_json::from_str("[\"trim\", {\"stop_at\": \"###\"}]").unwrap();
+        assert_eq!(parsed, vec![PostProcessor::Trim, PostProcessor::StopAt("###".into())]);
+    }
+}
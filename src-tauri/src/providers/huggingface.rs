@@ -0,0 +1,259 @@
+use std::time::Duration;
+
+use super::{check_status, context, truncate_at_stop, AiProvider, GenerationParams, PromptMessage, ProviderError, StreamLine};
+
+pub struct HuggingFace {
+    client: reqwest::Client,
+    model: String,
+    url: String,
+    token: String,
+}
+
+impl HuggingFace {
+    pub const API_BASE: &'static str = "https://api-inference.huggingface.co/models";
+    /// The Hub's model listing, used to search model ids.
+    const HUB_MODELS_URL: &'static str = "https://huggingface.co/api/models";
+
+    /// Streams legitimately run longer than the client's default total
+    /// timeout, so they get their own, more generous limit.
+    const STREAM_TIMEOUT: Duration = Duration::from_secs(600);
+
+    /// `token` normally comes from `credentials::require_token`.
+    pub fn new(client: reqwest::Client, model_id: &str, token: String) -> Self {
+        Self::with_base_url(client, Self::API_BASE, model_id, token)
+    }
+
+    /// Like `new`, against another inference endpoint such as a dedicated
+    /// deployment. `base_url` is the part before the model id.
+    pub fn with_base_url(client: reqwest::Client, base_url: &str, model_id: &str, token: String) -> Self {
+        HuggingFace {
+            client,
+            model: model_id.to_string(),
+            url: format!("{}/{}", base_url.trim_end_matches('/'), model_id),
+            token,
+        }
+    }
+
+    fn request(&self, payload: serde_json::Value) -> reqwest::RequestBuilder {
+        self.client.post(&self.url)
+            .bearer_auth(&self.token)
+            .json(&payload)
+    }
+
+    async fn post(&self, payload: serde_json::Value) -> Result<reqwest::Response, ProviderError> {
+        check_status(self.request(payload).send().await?).await
+    }
+
+    /// Runs the feature-extraction pipeline over `texts`, returning one vector
+    /// per input in order. Models that return per-token embeddings instead of
+    /// one pooled vector are rejected.
+    pub async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, ProviderError> {
+        let res = self.post(serde_json::json!({ "inputs": texts })).await?;
+        let not_pooled = || {
+            ProviderError::InvalidResponse(format!("{} does not return one fixed-length vector per input", self.model))
+        };
+        let vectors: Vec<Vec<f32>> = serde_json::from_value(res.json().await?).map_err(|_| not_pooled())?;
+        let dims = vectors.first().map_or(0, Vec::len);
+        if vectors.len() != texts.len() || dims == 0 || vectors.iter().any(|v| v.len() != dims) {
+            return Err(not_pooled());
+        }
+        Ok(vectors)
+    }
+
+    /// Runs a text-to-image model, returning the image bytes and their MIME
+    /// type. The body is the raw image rather than JSON.
+    pub async fn text_to_image(&self, prompt: &str) -> Result<(Vec<u8>, String), ProviderError> {
+        let res = self.post(serde_json::json!({ "inputs": prompt })).await?;
+        let mime = res
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("image/png")
+            .to_string();
+        if !mime.starts_with("image/") {
+            return Err(ProviderError::InvalidResponse(format!("{} returned {} instead of an image", self.model, mime)));
+        }
+        Ok((res.bytes().await?.to_vec(), mime))
+    }
+
+    /// Runs a speech-recognition model over an audio file's bytes, sent
+    /// as-is with `mime_type` as the content type, and returns the text.
+    pub async fn transcribe(&self, audio: &[u8], mime_type: &str) -> Result<String, ProviderError> {
+        let request = self
+            .client
+            .post(&self.url)
+            .bearer_auth(&self.token)
+            .header(reqwest::header::CONTENT_TYPE, mime_type)
+            .body(audio.to_vec());
+        let response: serde_json::Value = check_status(request.send().await?).await?.json().await?;
+        if let Some(error) = response.get("error") {
+            return Err(ProviderError::Model(error.as_str().map_or_else(|| error.to_string(), str::to_string)));
+        }
+        response["text"]
+            .as_str()
+            .map(|text| text.trim().to_string())
+            .ok_or_else(|| ProviderError::InvalidResponse("text not found in transcription response".into()))
+    }
+
+    /// Sends a one-token generation and returns the raw status, without
+    /// waiting for a cold model to load.
+    pub async fn probe(&self) -> Result<reqwest::StatusCode, reqwest::Error> {
+        let res = self.request(serde_json::json!({
+            "inputs": "ping",
+            "parameters": { "max_new_tokens": 1 },
+            "options": { "wait_for_model": false }
+        }))
+        .send()
+        .await?;
+        Ok(res.status())
+    }
+
+    /// Ids of up to `limit` Hub models whose id contains `query`, most
+    /// downloaded first. Needs no token.
+    pub async fn search_models(client: &reqwest::Client, query: &str, limit: usize) -> Result<Vec<String>, ProviderError> {
+        #[derive(serde::Deserialize)]
+        struct Model {
+            id: String,
+        }
+        let res = client
+            .get(Self::HUB_MODELS_URL)
+            .query(&[("search", query), ("sort", "downloads"), ("limit", &limit.to_string())])
+            .send()
+            .await?;
+        let models: Vec<Model> = check_status(res).await?.json().await?;
+        Ok(models.into_iter().map(|model| model.id).collect())
+    }
+}
+
+#[async_trait::async_trait]
+impl AiProvider for HuggingFace {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn endpoint(&self) -> &str {
+        &self.url
+    }
+
+    async fn generate(&self, prompt: &str, params: &GenerationParams) -> Result<String, ProviderError> {
+        let res = self.post(serde_json::json!({
+            "inputs": prompt,
+            "parameters": params
+        }))
+        .await?;
+
+        let response_json: serde_json::Value = res.json().await?;
+        // The API honours `stop` but leaves the matched sequence in the text.
+        parse_generated_text(&response_json).map(|text| truncate_at_stop(&text, &params.stop))
+    }
+
+    /// Flattens the chat into one prompt; the body is a server-sent-event
+    /// stream of text-generation tokens.
+    async fn stream(&self, messages: &[PromptMessage], params: &GenerationParams) -> Result<reqwest::Response, ProviderError> {
+        let request = self.request(serde_json::json!({
+            "inputs": context::format_prompt(messages),
+            "parameters": params,
+            "stream": true
+        }));
+        check_status(request.timeout(Self::STREAM_TIMEOUT).send().await?).await
+    }
+
+    fn parse_stream_data(&self, data: &str) -> StreamLine {
+        parse_stream_token(data).map_or(StreamLine::Skip, StreamLine::Token)
+    }
+}
+
+/// Pulls the reply out of a generation response. Pipelines differ in shape:
+/// an array of `{ "generated_text" }` objects (possibly nested one level
+/// deeper), a bare object, or a conversational reply whose `generated_text`
+/// is the chat with the new assistant turn last. An `{ "error" }` body is
+/// reported as the model's error.
+pub fn parse_generated_text(response: &serde_json::Value) -> Result<String, ProviderError> {
+    if let Some(error) = response.get("error") {
+        let message = match error {
+            serde_json::Value::String(message) => message.clone(),
+            other => other.to_string(),
+        };
+        return Err(ProviderError::Model(message));
+    }
+    find_generated_text(response)
+        .ok_or_else(|| ProviderError::InvalidResponse("generated text not found in response".into()))
+}
+
+fn find_generated_text(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::Array(items) => items.first().and_then(find_generated_text),
+        serde_json::Value::Object(object) => match object.get("generated_text")? {
+            serde_json::Value::String(text) => Some(text.clone()),
+            serde_json::Value::Array(chat) => chat.last()?["content"].as_str().map(str::to_string),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Extracts the token text from the data of one event of the HF
+/// text-generation stream. Returns `None` for special tokens and anything
+/// that isn't a token event.
+pub fn parse_stream_token(data: &str) -> Option<String> {
+    let event: serde_json::Value = serde_json::from_str(data).ok()?;
+    if event["token"]["special"].as_bool().unwrap_or(false) {
+        return None;
+    }
+    event["token"]["text"].as_str().map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(response: serde_json::Value) -> Result<String, ProviderError> {
+        parse_generated_text(&response)
+    }
+
+    #[test]
+    fn reads_array_of_objects() {
+        assert_eq!(parse(json!([{ "generated_text": "hello" }])).unwrap(), "hello");
+    }
+
+    #[test]
+    fn reads_nested_array() {
+        assert_eq!(parse(json!([[{ "generated_text": "nested" }]])).unwrap(), "nested");
+    }
+
+    #[test]
+    fn reads_bare_object() {
+        assert_eq!(parse(json!({ "generated_text": "bare", "details": {} })).unwrap(), "bare");
+    }
+
+    #[test]
+    fn reads_last_turn_of_conversational_reply() {
+        let response = json!([{
+            "generated_text": [
+                { "role": "user", "content": "hi" },
+                { "role": "assistant", "content": "hello there" }
+            ]
+        }]);
+        assert_eq!(parse(response).unwrap(), "hello there");
+    }
+
+    #[test]
+    fn surfaces_error_field() {
+        let err = parse(json!({ "error": "Model is overloaded" })).unwrap_err();
+        assert!(matches!(err, ProviderError::Model(message) if message == "Model is overloaded"));
+    }
+
+    #[test]
+    fn surfaces_structured_error_field() {
+        let err = parse(json!({ "error": ["input too long"] })).unwrap_err();
+        assert!(matches!(err, ProviderError::Model(message) if message.contains("input too long")));
+    }
+
+    #[test]
+    fn rejects_unknown_shapes() {
+        for response in [json!([]), json!({ "text": "x" }), json!("plain"), json!([{ "generated_text": 3 }])] {
+            assert!(matches!(parse(response), Err(ProviderError::InvalidResponse(_))));
+        }
+    }
+}
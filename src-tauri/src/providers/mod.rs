@@ -0,0 +1,393 @@
+pub mod huggingface;
+mod ollama;
+mod openai;
+mod sse;
+
+pub use huggingface::HuggingFace;
+pub use ollama::Ollama;
+pub use openai::OpenAiCompatible;
+pub use sse::{Interrupted, SseBuffer};
+
+use crate::error::AppError;
+use crate::{context, credentials, Role};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProviderError {
+    #[error("request failed: {0}")]
+    Network(#[from] reqwest::Error),
+    #[error("API error: {status} - {body}")]
+    Api {
+        status: u16,
+        body: String,
+        /// The server's `x-request-id`, for reporting issues to the provider.
+        request_id: Option<String>,
+        /// How long the server asked us to wait, from `Retry-After`.
+        retry_after: Option<std::time::Duration>,
+    },
+    #[error("unexpected response: {0}")]
+    InvalidResponse(String),
+    /// The provider answered with an error of its own, e.g. a model that
+    /// can't handle the input.
+    #[error("model error: {0}")]
+    Model(String),
+}
+
+/// Most stop sequences a request may carry.
+const MAX_STOP_SEQUENCES: usize = 8;
+
+/// A generation parameter outside what `GenerationParams::validate` allows.
+#[derive(Debug, thiserror::Error)]
+#[error("{field} {reason}")]
+pub struct ValidationError {
+    pub field: &'static str,
+    pub reason: String,
+}
+
+fn check_range<T>(field: &'static str, value: Option<T>, range: std::ops::RangeInclusive<T>) -> Result<(), ValidationError>
+where
+    T: PartialOrd + std::fmt::Display + Copy,
+{
+    match value {
+        Some(value) if !range.contains(&value) => Err(ValidationError {
+            field,
+            reason: format!("must be between {} and {}, got {}", range.start(), range.end(), value),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Optional sampling controls. Fields left as `None` are omitted from the
+/// request entirely so the model falls back to its own defaults.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct GenerationParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_new_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repetition_penalty: Option<f32>,
+    /// Strings that end the generation when produced. The reply is cut at the
+    /// first one, which is not included.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    /// Shape of the reply. Not sent as a sampling parameter; providers with a
+    /// native JSON mode switch it on themselves.
+    #[serde(default, skip_serializing)]
+    pub response_format: ResponseFormat,
+}
+
+impl GenerationParams {
+    /// Checks every set field against its allowed range, plus the limits on
+    /// stop sequences. Unset fields are always fine.
+    pub fn validate(&self) -> Result<(), AppError> {
+        check_range("temperature", self.temperature, 0.0..=2.0)?;
+        check_range("top_p", self.top_p, 0.0..=1.0)?;
+        check_range("max_new_tokens", self.max_new_tokens, 1..=4096)?;
+        check_range("repetition_penalty", self.repetition_penalty, 0.1..=2.0)?;
+        if self.stop.len() > MAX_STOP_SEQUENCES {
+            return Err(ValidationError {
+                field: "stop",
+                reason: format!("allows at most {} sequences, got {}", MAX_STOP_SEQUENCES, self.stop.len()),
+            }
+            .into());
+        }
+        if self.stop.iter().any(|s| s.is_empty()) {
+            return Err(ValidationError { field: "stop", reason: "sequences must not be empty".into() }.into());
+        }
+        Ok(())
+    }
+
+    /// Fills the fields left unset here from `fallback`.
+    pub fn or(self, fallback: GenerationParams) -> Self {
+        GenerationParams {
+            temperature: self.temperature.or(fallback.temperature),
+            max_new_tokens: self.max_new_tokens.or(fallback.max_new_tokens),
+            top_p: self.top_p.or(fallback.top_p),
+            repetition_penalty: self.repetition_penalty.or(fallback.repetition_penalty),
+            stop: if self.stop.is_empty() { fallback.stop } else { self.stop },
+            response_format: self.response_format,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// One turn of a chat as sent to a provider.
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct PromptMessage {
+    pub role: Role,
+    pub content: String,
+}
+
+impl PromptMessage {
+    pub fn new(role: Role, content: &str) -> Self {
+        PromptMessage {
+            role,
+            content: content.to_string(),
+        }
+    }
+}
+
+/// What one event of a streamed response holds.
+pub enum StreamLine {
+    Token(String),
+    /// The server signalled the end of the stream.
+    Done,
+    /// Blank lines, comments, keep-alives and anything else to ignore.
+    Skip,
+}
+
+/// A text-generation backend. Implementations own their endpoint and
+/// credentials so commands only deal with prompts and replies.
+#[async_trait::async_trait]
+pub trait AiProvider: Send + Sync {
+    /// Model id requests are sent to, used when recording usage.
+    fn model(&self) -> &str;
+
+    /// URL requests are sent to, used to check the host is reachable first.
+    fn endpoint(&self) -> &str;
+
+    async fn generate(&self, prompt: &str, params: &GenerationParams) -> Result<String, ProviderError>;
+
+    /// Answers a chat. The default flattens `messages` into one prompt for
+    /// `generate`; providers with a native chat format override it.
+    async fn chat(&self, messages: &[PromptMessage], params: &GenerationParams) -> Result<String, ProviderError> {
+        self.generate(&context::format_prompt(messages), params).await
+    }
+
+    /// Starts a streaming reply and returns the response once the server has
+    /// accepted it; its body is read through an `SseBuffer`, and the data of
+    /// each event parsed with `parse_stream_data`.
+    async fn stream(&self, _messages: &[PromptMessage], _params: &GenerationParams) -> Result<reqwest::Response, ProviderError> {
+        Err(ProviderError::InvalidResponse(format!("{} does not support streaming", self.model())))
+    }
+
+    fn parse_stream_data(&self, _data: &str) -> StreamLine {
+        StreamLine::Skip
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    #[default]
+    HuggingFace,
+    Ollama,
+    OpenAi,
+}
+
+/// Provider defaults, read from the environment at startup and kept in
+/// managed state. Commands fall back to `default_kind` when the caller
+/// doesn't name a provider.
+pub struct ProviderConfig {
+    pub default_kind: ProviderKind,
+    pub ollama_url: String,
+    pub ollama_model: String,
+    pub openai_url: String,
+    pub openai_api_key: Option<String>,
+    pub openai_model: String,
+}
+
+impl ProviderConfig {
+    pub fn from_env() -> Self {
+        let default_kind = match std::env::var("AIOS_PROVIDER").as_deref() {
+            Ok("ollama") => ProviderKind::Ollama,
+            Ok("openai") => ProviderKind::OpenAi,
+            _ => ProviderKind::HuggingFace,
+        };
+        ProviderConfig {
+            default_kind,
+            ollama_url: std::env::var("OLLAMA_URL").unwrap_or_else(|_| Ollama::DEFAULT_URL.to_string()),
+            ollama_model: std::env::var("OLLAMA_MODEL").unwrap_or_else(|_| Ollama::DEFAULT_MODEL.to_string()),
+            openai_url: std::env::var("OPENAI_BASE_URL").unwrap_or_else(|_| OpenAiCompatible::DEFAULT_URL.to_string()),
+            openai_api_key: std::env::var("OPENAI_API_KEY").ok(),
+            openai_model: std::env::var("OPENAI_MODEL").unwrap_or_else(|_| OpenAiCompatible::DEFAULT_MODEL.to_string()),
+        }
+    }
+}
+
+/// A complete provider setup passed in from the UI, e.g. to try it with
+/// `test_provider` before saving. Unset fields take the same defaults as the
+/// environment does; a Hugging Face setup without a token uses the stored one.
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct ProviderSettings {
+    pub kind: ProviderKind,
+    pub base_url: Option<String>,
+    pub model: Option<String>,
+    pub token: Option<String>,
+}
+
+impl ProviderSettings {
+    pub fn build(&self, client: reqwest::Client) -> Result<Box<dyn AiProvider>, AppError> {
+        let base_url = self.base_url.as_deref().filter(|url| !url.is_empty());
+        let model = self.model.as_deref().filter(|model| !model.is_empty());
+        let token = self.token.clone().filter(|token| !token.is_empty());
+        Ok(match self.kind {
+            ProviderKind::HuggingFace => {
+                let model = model.ok_or_else(|| AppError::InvalidParams("a Hugging Face model id is required".into()))?;
+                let token = match token {
+                    Some(token) => token,
+                    None => credentials::require_token()?,
+                };
+                Box::new(HuggingFace::with_base_url(client, base_url.unwrap_or(HuggingFace::API_BASE), model, token))
+            }
+            ProviderKind::Ollama => Box::new(Ollama::new(
+                client,
+                base_url.unwrap_or(Ollama::DEFAULT_URL),
+                model.unwrap_or(Ollama::DEFAULT_MODEL),
+            )),
+            ProviderKind::OpenAi => Box::new(OpenAiCompatible::new(
+                client,
+                base_url.unwrap_or(OpenAiCompatible::DEFAULT_URL),
+                token.as_deref(),
+                model.unwrap_or(OpenAiCompatible::DEFAULT_MODEL),
+            )),
+        })
+    }
+}
+
+/// Builds the provider for `kind`. `hf_model` is the Hugging Face model id
+/// and only applies to that provider; the others use their configured model.
+/// Only the Hugging Face provider needs a token.
+pub fn build(
+    kind: ProviderKind,
+    config: &ProviderConfig,
+    client: reqwest::Client,
+    hf_model: &str,
+) -> Result<Box<dyn AiProvider>, AppError> {
+    Ok(match kind {
+        ProviderKind::HuggingFace => Box::new(HuggingFace::new(client, hf_model, credentials::require_token()?)),
+        ProviderKind::Ollama => Box::new(Ollama::new(client, &config.ollama_url, &config.ollama_model)),
+        ProviderKind::OpenAi => Box::new(OpenAiCompatible::new(
+            client,
+            &config.openai_url,
+            config.openai_api_key.as_deref(),
+            &config.openai_model,
+        )),
+    })
+}
+
+/// Turns a non-success response into `ProviderError::Api`, passing
+/// successful responses through untouched.
+async fn check_status(res: reqwest::Response) -> Result<reqwest::Response, ProviderError> {
+    if res.status().is_success() {
+        return Ok(res);
+    }
+    let status = res.status().as_u16();
+    let request_id = res
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let retry_after = res
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_retry_after);
+    let body = res.text().await?;
+    tracing::warn!(
+        status,
+        request_id = request_id.as_deref().unwrap_or("-"),
+        body = %body.chars().take(200).collect::<String>(),
+        "API request failed"
+    );
+    Err(ProviderError::Api { status, body, request_id, retry_after })
+}
+
+/// Reads a `Retry-After` value, given either as seconds or as an HTTP date.
+fn parse_retry_after(value: &str) -> Option<std::time::Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(std::time::Duration::from_secs(secs));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    // A date already in the past means "now".
+    Some(at.duration_since(std::time::SystemTime::now()).unwrap_or_default())
+}
+
+/// Cuts `text` at the first occurrence of any of the `stop` strings, for
+/// backends that don't stop on them exactly.
+pub fn truncate_at_stop(text: &str, stop: &[String]) -> String {
+    let end = stop
+        .iter()
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| text.find(s.as_str()))
+        .min()
+        .unwrap_or(text.len());
+    text[..end].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_field(params: GenerationParams) -> Option<&'static str> {
+        match params.validate() {
+            Ok(()) => None,
+            Err(AppError::Validation(err)) => Some(err.field),
+            Err(other) => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn accepts_unset_params() {
+        assert_eq!(invalid_field(GenerationParams::default()), None);
+    }
+
+    #[test]
+    fn temperature_bounds() {
+        for (value, field) in [(0.0, None), (2.0, None), (-0.01, Some("temperature")), (2.01, Some("temperature"))] {
+            let params = GenerationParams { temperature: Some(value), ..Default::default() };
+            assert_eq!(invalid_field(params), field, "temperature {}", value);
+        }
+        let params = GenerationParams { temperature: Some(f32::NAN), ..Default::default() };
+        assert_eq!(invalid_field(params), Some("temperature"));
+    }
+
+    #[test]
+    fn top_p_bounds() {
+        for (value, field) in [(0.0, None), (1.0, None), (-0.01, Some("top_p")), (1.01, Some("top_p"))] {
+            let params = GenerationParams { top_p: Some(value), ..Default::default() };
+            assert_eq!(invalid_field(params), field, "top_p {}", value);
+        }
+    }
+
+    #[test]
+    fn max_new_tokens_bounds() {
+        for (value, field) in [(1, None), (4096, None), (0, Some("max_new_tokens")), (4097, Some("max_new_tokens"))] {
+            let params = GenerationParams { max_new_tokens: Some(value), ..Default::default() };
+            assert_eq!(invalid_field(params), field, "max_new_tokens {}", value);
+        }
+    }
+
+    #[test]
+    fn repetition_penalty_bounds() {
+        for (value, field) in [
+            (0.1, None),
+            (2.0, None),
+            (0.09, Some("repetition_penalty")),
+            (2.01, Some("repetition_penalty")),
+        ] {
+            let params = GenerationParams { repetition_penalty: Some(value), ..Default::default() };
+            assert_eq!(invalid_field(params), field, "repetition_penalty {}", value);
+        }
+    }
+
+    #[test]
+    fn stop_sequence_limits() {
+        let params = GenerationParams { stop: vec!["\n".into(); MAX_STOP_SEQUENCES], ..Default::default() };
+        assert_eq!(invalid_field(params), None);
+        let params = GenerationParams { stop: vec!["\n".into(); MAX_STOP_SEQUENCES + 1], ..Default::default() };
+        assert_eq!(invalid_field(params), Some("stop"));
+        let params = GenerationParams { stop: vec![String::new()], ..Default::default() };
+        assert_eq!(invalid_field(params), Some("stop"));
+    }
+}
@@ -0,0 +1,76 @@
+use super::{check_status, AiProvider, GenerationParams, ProviderError, ResponseFormat};
+
+/// A local Ollama server, talked to over its native `/api/generate` endpoint.
+pub struct Ollama {
+    client: reqwest::Client,
+    base_url: String,
+    model: String,
+}
+
+impl Ollama {
+    pub const DEFAULT_URL: &'static str = "http://localhost:11434";
+    pub const DEFAULT_MODEL: &'static str = "llama3";
+
+    pub fn new(client: reqwest::Client, base_url: &str, model: &str) -> Self {
+        Ollama {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl AiProvider for Ollama {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn endpoint(&self) -> &str {
+        &self.base_url
+    }
+
+    async fn generate(&self, prompt: &str, params: &GenerationParams) -> Result<String, ProviderError> {
+        let mut payload = serde_json::json!({
+            "model": self.model,
+            "prompt": prompt,
+            "stream": false,
+            "options": options(params)
+        });
+        if params.response_format == ResponseFormat::Json {
+            payload["format"] = "json".into();
+        }
+
+        let res = self.client.post(format!("{}/api/generate", self.base_url))
+            .json(&payload)
+            .send()
+            .await?;
+        let response_json: serde_json::Value = check_status(res).await?.json().await?;
+        response_json["response"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| ProviderError::InvalidResponse("response field not found".into()))
+    }
+}
+
+/// Maps the shared parameters onto Ollama's `options` names, leaving unset
+/// fields out.
+fn options(params: &GenerationParams) -> serde_json::Value {
+    let mut options = serde_json::Map::new();
+    if let Some(temperature) = params.temperature {
+        options.insert("temperature".into(), temperature.into());
+    }
+    if let Some(max_new_tokens) = params.max_new_tokens {
+        options.insert("num_predict".into(), max_new_tokens.into());
+    }
+    if let Some(top_p) = params.top_p {
+        options.insert("top_p".into(), top_p.into());
+    }
+    if let Some(repetition_penalty) = params.repetition_penalty {
+        options.insert("repeat_penalty".into(), repetition_penalty.into());
+    }
+    if !params.stop.is_empty() {
+        options.insert("stop".into(), params.stop.clone().into());
+    }
+    serde_json::Value::Object(options)
+}
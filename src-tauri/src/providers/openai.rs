@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use super::{check_status, AiProvider, GenerationParams, PromptMessage, ProviderError, ResponseFormat, StreamLine};
+use crate::Role;
+
+/// Any server speaking the OpenAI `/v1/chat/completions` API, such as LM
+/// Studio, vLLM or Ollama's compatibility endpoint.
+pub struct OpenAiCompatible {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+impl OpenAiCompatible {
+    pub const DEFAULT_URL: &'static str = "http://localhost:1234";
+    pub const DEFAULT_MODEL: &'static str = "local-model";
+
+    const STREAM_TIMEOUT: Duration = Duration::from_secs(600);
+
+    /// `base_url` is the server root, without the `/v1` suffix.
+    pub fn new(client: reqwest::Client, base_url: &str, api_key: Option<&str>, model: &str) -> Self {
+        OpenAiCompatible {
+            client,
+            base_url: base_url.trim_end_matches('/').trim_end_matches("/v1").to_string(),
+            api_key: api_key.filter(|key| !key.is_empty()).map(str::to_string),
+            model: model.to_string(),
+        }
+    }
+
+    fn request(&self, messages: &[PromptMessage], params: &GenerationParams, stream: bool) -> reqwest::RequestBuilder {
+        let mut body = serde_json::json!({
+            "model": self.model,
+            "messages": messages,
+            "stream": stream
+        });
+        if let Some(temperature) = params.temperature {
+            body["temperature"] = temperature.into();
+        }
+        if let Some(max_new_tokens) = params.max_new_tokens {
+            body["max_tokens"] = max_new_tokens.into();
+        }
+        if let Some(top_p) = params.top_p {
+            body["top_p"] = top_p.into();
+        }
+        if !params.stop.is_empty() {
+            body["stop"] = params.stop.clone().into();
+        }
+        if params.response_format == ResponseFormat::Json {
+            body["response_format"] = serde_json::json!({ "type": "json_object" });
+        }
+        let request = self.client.post(format!("{}/v1/chat/completions", self.base_url)).json(&body);
+        self.authorize(request)
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.api_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        }
+    }
+
+    /// Ids of the models the server offers, from `/v1/models`.
+    pub async fn list_models(&self) -> Result<Vec<String>, ProviderError> {
+        let request = self.authorize(self.client.get(format!("{}/v1/models", self.base_url)));
+        let res = check_status(request.send().await?).await?;
+        let response_json: serde_json::Value = res.json().await?;
+        let data = response_json["data"]
+            .as_array()
+            .ok_or_else(|| ProviderError::InvalidResponse("data field not found".into()))?;
+        Ok(data.iter().filter_map(|m| m["id"].as_str().map(str::to_string)).collect())
+    }
+}
+
+#[async_trait::async_trait]
+impl AiProvider for OpenAiCompatible {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn endpoint(&self) -> &str {
+        &self.base_url
+    }
+
+    async fn generate(&self, prompt: &str, params: &GenerationParams) -> Result<String, ProviderError> {
+        self.chat(&[PromptMessage::new(Role::User, prompt)], params).await
+    }
+
+    async fn chat(&self, messages: &[PromptMessage], params: &GenerationParams) -> Result<String, ProviderError> {
+        let res = check_status(self.request(messages, params, false).send().await?).await?;
+        let response_json: serde_json::Value = res.json().await?;
+        response_json["choices"][0]["message"]["content"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| ProviderError::InvalidResponse("choices[0].message.content not found".into()))
+    }
+
+    async fn stream(&self, messages: &[PromptMessage], params: &GenerationParams) -> Result<reqwest::Response, ProviderError> {
+        let request = self.request(messages, params, true).timeout(Self::STREAM_TIMEOUT);
+        check_status(request.send().await?).await
+    }
+
+    fn parse_stream_data(&self, data: &str) -> StreamLine {
+        if data.trim() == "[DONE]" {
+            return StreamLine::Done;
+        }
+        serde_json::from_str::<serde_json::Value>(data)
+            .ok()
+            .and_then(|event| event["choices"][0]["delta"]["content"].as_str().map(str::to_string))
+            .filter(|text| !text.is_empty())
+            .map_or(StreamLine::Skip, StreamLine::Token)
+    }
+}
@@ -0,0 +1,127 @@
+/// Reassembles a server-sent event stream from network reads, which can
+/// split a line (or a UTF-8 character) anywhere. Incomplete lines wait in
+/// the buffer until their newline arrives; comment lines such as
+/// keep-alives are dropped, and fields other than `data` are ignored.
+#[derive(Default)]
+pub struct SseBuffer {
+    buf: Vec<u8>,
+    /// `data` lines of the event being read, not yet ended by a blank line.
+    data: Vec<String>,
+}
+
+/// The stream ended partway through an event; holds what had arrived of it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Interrupted(pub String);
+
+impl SseBuffer {
+    /// Adds one read and returns the data of every event it completed, in
+    /// order. Multi-line data is joined with newlines.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buf.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(pos) = self.buf.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buf.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            events.extend(self.line(line.trim_end_matches(['\n', '\r'])));
+        }
+        events
+    }
+
+    fn line(&mut self, line: &str) -> Option<String> {
+        if line.is_empty() {
+            return self.take_event();
+        }
+        if let Some(data) = line.strip_prefix("data:") {
+            self.data.push(data.strip_prefix(' ').unwrap_or(data).to_string());
+        }
+        None
+    }
+
+    fn take_event(&mut self) -> Option<String> {
+        (!self.data.is_empty()).then(|| std::mem::take(&mut self.data).join("\n"))
+    }
+
+    /// Call once the stream has closed. Some servers don't end the last
+    /// event with a blank line, or its line with a newline; such an event
+    /// still counts if its data parses as JSON (or is `[DONE]`). Anything
+    /// else left over means the stream was cut off mid-message.
+    pub fn finish(mut self) -> Result<Option<String>, Interrupted> {
+        if !self.buf.is_empty() {
+            let rest = String::from_utf8_lossy(&self.buf).trim_end_matches('\r').to_string();
+            // Every field and comment has a colon; without one the line was
+            // cut off inside its field name.
+            if !rest.contains(':') {
+                return Err(Interrupted(rest));
+            }
+            self.line(&rest);
+        }
+        let Some(data) = self.take_event() else {
+            return Ok(None);
+        };
+        if data == "[DONE]" || serde_json::from_str::<serde::de::IgnoredAny>(&data).is_ok() {
+            Ok(Some(data))
+        } else {
+            Err(Interrupted(data))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds `stream` in pieces cut at `splits` and collects every event.
+    fn feed(stream: &str, splits: &[usize]) -> (Vec<String>, Result<Option<String>, Interrupted>) {
+        let bytes = stream.as_bytes();
+        let mut buffer = SseBuffer::default();
+        let mut events = Vec::new();
+        let mut start = 0;
+        for &end in splits.iter().chain([bytes.len()].iter()) {
+            events.extend(buffer.push(&bytes[start..end]));
+            start = end;
+        }
+        (events, buffer.finish())
+    }
+
+    const STREAM: &str = "data: {\"token\":{\"text\":\"hé\"}}\n\n: keep-alive\n\ndata: {\"token\":{\"text\":\"llo\"}}\r\n\r\n";
+
+    fn expected() -> Vec<String> {
+        vec![r#"{"token":{"text":"hé"}}"#.to_string(), r#"{"token":{"text":"llo"}}"#.to_string()]
+    }
+
+    #[test]
+    fn reassembles_events_split_at_any_byte() {
+        for split in 0..=STREAM.len() {
+            let (events, rest) = feed(STREAM, &[split]);
+            assert_eq!(events, expected(), "split at {}", split);
+            assert_eq!(rest, Ok(None));
+        }
+    }
+
+    #[test]
+    fn handles_one_byte_reads_through_a_multi_byte_character() {
+        let splits: Vec<usize> = (1..STREAM.len()).collect();
+        assert_eq!(feed(STREAM, &splits), (expected(), Ok(None)));
+    }
+
+    #[test]
+    fn joins_multi_line_data_and_ignores_other_fields() {
+        let (events, _) = feed("event: message\nid: 1\ndata: one\ndata:two\n\n", &[17, 30]);
+        assert_eq!(events, vec!["one\ntwo".to_string()]);
+    }
+
+    #[test]
+    fn accepts_an_unterminated_final_event_that_is_whole() {
+        let (events, rest) = feed("data: {\"a\":1}\n\ndata: {\"b\":2}", &[5]);
+        assert_eq!(events, vec![r#"{"a":1}"#.to_string()]);
+        assert_eq!(rest, Ok(Some(r#"{"b":2}"#.to_string())));
+        assert_eq!(feed("data: [DONE]\n", &[]).1, Ok(Some("[DONE]".to_string())));
+    }
+
+    #[test]
+    fn reports_a_stream_closed_mid_message() {
+        assert_eq!(feed("data: {\"a\":1}\n\ndata: {\"tok", &[10]).1, Err(Interrupted(r#"{"tok"#.to_string())));
+        assert_eq!(feed("data: {\"a\":1}\n\nda", &[]).1, Err(Interrupted("da".to_string())));
+        assert_eq!(feed(": keep-alive", &[]).1, Ok(None));
+    }
+}
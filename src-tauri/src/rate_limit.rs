@@ -0,0 +1,85 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::AppError;
+
+/// Longest `acquire` will wait for a permit before giving up.
+const MAX_WAIT: Duration = Duration::from_secs(2);
+
+struct Bucket {
+    /// Permits available now; goes negative while callers wait on reserved ones.
+    tokens: f64,
+    requests_per_minute: u32,
+    last_refill: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        let capacity = self.requests_per_minute as f64;
+        self.tokens = (self.tokens + elapsed * capacity / 60.0).min(capacity);
+        self.last_refill = now;
+    }
+}
+
+/// Token bucket shared by everything that calls a provider, refilled
+/// continuously at `requests_per_minute`. A rate of 0 disables limiting.
+pub struct RateLimiter(Mutex<Bucket>);
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct RateLimitStatus {
+    remaining: u32,
+    requests_per_minute: u32,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_minute: u32) -> Self {
+        RateLimiter(Mutex::new(Bucket {
+            tokens: requests_per_minute as f64,
+            requests_per_minute,
+            last_refill: Instant::now(),
+        }))
+    }
+
+    /// Takes a permit, waiting briefly if one is about to free up. Returns
+    /// `AppError::RateLimited` when the wait would be longer than that.
+    pub async fn acquire(&self) -> Result<(), AppError> {
+        let wait = {
+            let mut bucket = self.0.lock()?;
+            if bucket.requests_per_minute == 0 {
+                return Ok(());
+            }
+            bucket.refill();
+            let wait = Duration::from_secs_f64((1.0 - bucket.tokens).max(0.0) * 60.0 / bucket.requests_per_minute as f64);
+            if wait > MAX_WAIT {
+                return Err(AppError::RateLimited {
+                    retry_after_secs: wait.as_secs_f64().ceil() as u64,
+                });
+            }
+            bucket.tokens -= 1.0;
+            wait
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        Ok(())
+    }
+
+    pub fn set_rate(&self, requests_per_minute: u32) -> Result<(), AppError> {
+        let mut bucket = self.0.lock()?;
+        bucket.refill();
+        bucket.requests_per_minute = requests_per_minute;
+        bucket.tokens = bucket.tokens.min(requests_per_minute as f64);
+        Ok(())
+    }
+
+    pub fn status(&self) -> Result<RateLimitStatus, AppError> {
+        let mut bucket = self.0.lock()?;
+        bucket.refill();
+        Ok(RateLimitStatus {
+            remaining: bucket.tokens.max(0.0) as u32,
+            requests_per_minute: bucket.requests_per_minute,
+        })
+    }
+}
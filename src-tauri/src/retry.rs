@@ -0,0 +1,84 @@
+use std::future::Future;
+use std::time::Duration;
+
+use crate::providers::ProviderError;
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// A `Retry-After` longer than this isn't waited out; the 429 is returned.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+/// Runs `attempt` until it succeeds, fails with a non-retryable error, or
+/// `max_retries` retries have been spent. Retries 503s (model loading) and
+/// transient network errors with exponential backoff starting at one second;
+/// a 503 that reports `estimated_time` waits that long instead. A 429 waits
+/// for its `Retry-After`, or the backoff if it has none.
+pub async fn with_backoff<T, F, Fut>(max_retries: u32, attempt: F) -> Result<T, ProviderError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ProviderError>>,
+{
+    with_backoff_notify(max_retries, attempt, |_| {}).await
+}
+
+/// Like `with_backoff`, but calls `on_wait` with a short human-readable
+/// status (e.g. "model loading, ~20s") before each wait.
+pub async fn with_backoff_notify<T, F, Fut>(
+    max_retries: u32,
+    mut attempt: F,
+    mut on_wait: impl FnMut(&str),
+) -> Result<T, ProviderError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ProviderError>>,
+{
+    let mut backoff = INITIAL_BACKOFF;
+    let mut retries = 0;
+    loop {
+        let err = match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(err) => err,
+        };
+        if retries >= max_retries {
+            return Err(err);
+        }
+        match retry_delay(&err, backoff) {
+            Some(wait) => {
+                let status = wait_status(&err, wait);
+                tracing::info!(%status, wait_ms = wait.as_millis() as u64, retry = retries + 1, "retrying");
+                on_wait(&status);
+                tokio::time::sleep(wait).await
+            }
+            None => return Err(err),
+        }
+        retries += 1;
+        backoff *= 2;
+    }
+}
+
+/// How long to wait before retrying `err`, or `None` if it isn't worth
+/// retrying.
+fn retry_delay(err: &ProviderError, backoff: Duration) -> Option<Duration> {
+    match err {
+        ProviderError::Api { status: 503, body, .. } => Some(estimated_time(body).unwrap_or(backoff)),
+        ProviderError::Api { status: 429, retry_after: Some(wait), .. } => (*wait <= MAX_RETRY_AFTER).then_some(*wait),
+        ProviderError::Api { status: 429, .. } => Some(backoff),
+        ProviderError::Network(e) if e.is_timeout() || e.is_connect() || e.is_request() => Some(backoff),
+        _ => None,
+    }
+}
+
+fn wait_status(err: &ProviderError, wait: Duration) -> String {
+    let secs = wait.as_secs_f64().ceil() as u64;
+    match err {
+        ProviderError::Api { status: 503, .. } => format!("model loading, ~{}s", secs),
+        ProviderError::Api { status: 429, .. } => format!("rate limited by the provider, retrying in {}s", secs),
+        _ => format!("connection problem, retrying in {}s", secs),
+    }
+}
+
+/// Reads the `estimated_time` (seconds) HF includes in a model-loading 503.
+fn estimated_time(body: &str) -> Option<Duration> {
+    let body: serde_json::Value = serde_json::from_str(body).ok()?;
+    let secs = body["estimated_time"].as_f64()?;
+    (secs.is_finite() && secs > 0.0).then(|| Duration::from_secs_f64(secs))
+}
@@ -0,0 +1,28 @@
+use tauri::Manager;
+
+use crate::config::ModelState;
+use crate::credentials;
+use crate::data_dir::DataDir;
+use crate::error::AppError;
+use crate::generation::current_model;
+
+/// What a first run still needs, for the onboarding screen.
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct SetupStatus {
+    /// A Hugging Face token is saved or set in `HF_TOKEN`.
+    has_token: bool,
+    data_dir_writable: bool,
+    model_selected: bool,
+}
+
+/// Reports what setup is missing. Only local state is checked, never the
+/// network, so it is fast enough to call while the splash screen shows.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub fn is_configured(app: tauri::AppHandle) -> Result<SetupStatus, AppError> {
+    Ok(SetupStatus {
+        has_token: credentials::hf_token().is_some(),
+        data_dir_writable: app.state::<DataDir>().is_writable(),
+        model_selected: !current_model(&app.state::<ModelState>())?.trim().is_empty(),
+    })
+}
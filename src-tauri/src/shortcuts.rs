@@ -0,0 +1,58 @@
+use tauri::{GlobalShortcutManager, Manager};
+
+use crate::config::ConfigStore;
+use crate::error::AppError;
+
+/// Global hotkeys, as Tauri accelerator strings such as
+/// `CmdOrCtrl+Shift+N`. An empty string leaves that action unbound.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Shortcuts {
+    /// Emits `new-chat` to the main window.
+    pub new_chat: String,
+    /// Emits `focus-input` to the main window.
+    pub focus_input: String,
+}
+
+impl Default for Shortcuts {
+    fn default() -> Self {
+        Shortcuts {
+            new_chat: "CmdOrCtrl+Shift+N".to_string(),
+            focus_input: "CmdOrCtrl+Shift+Space".to_string(),
+        }
+    }
+}
+
+/// Replaces whatever shortcuts are registered with `shortcuts`. Fails if an
+/// accelerator is invalid or already taken by another app.
+pub fn register(app: &tauri::AppHandle, shortcuts: &Shortcuts) -> Result<(), AppError> {
+    let mut manager = app.global_shortcut_manager();
+    manager.unregister_all().map_err(|e| AppError::Shortcut(e.to_string()))?;
+    for (accelerator, event) in [(&shortcuts.new_chat, "new-chat"), (&shortcuts.focus_input, "focus-input")] {
+        if accelerator.is_empty() {
+            continue;
+        }
+        let app = app.clone();
+        manager.register(accelerator, move || {
+            if let Some(window) = app.get_window("main") {
+                let _ = window.emit(event, ());
+            }
+        })
+        .map_err(|e| AppError::Shortcut(format!("could not register {}: {}", accelerator, e)))?;
+    }
+    Ok(())
+}
+
+pub fn unregister_all(app: &tauri::AppHandle) -> Result<(), AppError> {
+    app.global_shortcut_manager()
+        .unregister_all()
+        .map_err(|e| AppError::Shortcut(e.to_string()))
+}
+
+/// Registers new shortcuts and saves them once they are in effect.
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub fn set_shortcuts(app: tauri::AppHandle, shortcuts: Shortcuts) -> Result<(), AppError> {
+    register(&app, &shortcuts)?;
+    app.state::<ConfigStore>().update(|c| c.shortcuts = shortcuts)
+}
@@ -0,0 +1,87 @@
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::AppError;
+use crate::ChatMessage;
+
+/// Where the full text of oversized messages is kept, under the data dir.
+const DIR_NAME: &str = "messages";
+
+fn dir(data_dir: &Path) -> PathBuf {
+    data_dir.join(DIR_NAME)
+}
+
+/// The form of `message` to store when it is over `max_chars` characters:
+/// the full content goes to a file named by its hash, so branch copies
+/// share it, and `content` keeps the first `max_chars` characters as a
+/// preview. Other messages, or any with `max_chars` 0, come back as is.
+pub fn offload(data_dir: &Path, message: &ChatMessage, max_chars: usize) -> Result<ChatMessage, AppError> {
+    let Some((cut, _)) = message.content.char_indices().nth(max_chars).filter(|_| max_chars > 0) else {
+        return Ok(message.clone());
+    };
+    let dir = dir(data_dir);
+    std::fs::create_dir_all(&dir)?;
+    let file_name = format!("{:x}.txt", Sha256::digest(message.content.as_bytes()));
+    let path = dir.join(&file_name);
+    if !path.exists() {
+        std::fs::write(&path, &message.content)?;
+    }
+    tracing::debug!(chars = message.content.chars().count(), file = %file_name, "message content moved to sidecar");
+    Ok(ChatMessage {
+        content: message.content[..cut].to_string(),
+        content_file: Some(file_name),
+        truncated: true,
+        ..message.clone()
+    })
+}
+
+/// Puts the full content back into a message loaded as a preview. If the
+/// file is gone the preview stays, still marked `truncated`.
+pub fn rehydrate(data_dir: &Path, message: &mut ChatMessage) {
+    let Some(file_name) = &message.content_file else {
+        return;
+    };
+    match std::fs::read_to_string(dir(data_dir).join(file_name)) {
+        Ok(content) => {
+            message.content = content;
+            message.truncated = false;
+        }
+        Err(e) => tracing::warn!(id = %message.id, file = %file_name, error = %e, "message sidecar unreadable, keeping preview"),
+    }
+}
+
+pub fn rehydrate_all(data_dir: &Path, messages: &mut [ChatMessage]) {
+    for message in messages {
+        rehydrate(data_dir, message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db, Role};
+
+    #[test]
+    fn oversized_message_round_trips() {
+        let data_dir = std::env::temp_dir().join(format!("sidecar-test-{}", uuid::Uuid::new_v4()));
+        let conn = db::open(Path::new(":memory:")).unwrap();
+        let content = "é".repeat(100);
+        let message = ChatMessage::new("chat", content.clone(), Role::Assistant);
+
+        let stored = offload(&data_dir, &message, 10).unwrap();
+        assert_eq!(stored.content, "é".repeat(10));
+        db::insert_message(&conn, &stored).unwrap();
+
+        let mut loaded = db::load_messages(&conn, Some("chat")).unwrap();
+        assert_eq!(loaded[0].content, stored.content);
+        assert!(loaded[0].truncated);
+        rehydrate_all(&data_dir, &mut loaded);
+        assert_eq!(loaded[0].content, content);
+        assert!(!loaded[0].truncated);
+
+        let small = ChatMessage::new("chat", "short".into(), Role::User);
+        assert!(offload(&data_dir, &small, 10).unwrap().content_file.is_none());
+        std::fs::remove_dir_all(&data_dir).unwrap();
+    }
+}
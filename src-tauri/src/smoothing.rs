@@ -0,0 +1,171 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// How `ai-token` events are cut when streaming. `Token` forwards provider
+/// chunks as they arrive; `Word` and `Char` re-split them and emit the
+/// pieces at a steady pace, which reads smoother than bursty raw chunks.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamGranularity {
+    #[default]
+    Token,
+    Word,
+    Char,
+}
+
+impl StreamGranularity {
+    fn cadence(self) -> Option<Duration> {
+        match self {
+            StreamGranularity::Token => None,
+            StreamGranularity::Word => Some(Duration::from_millis(40)),
+            StreamGranularity::Char => Some(Duration::from_millis(10)),
+        }
+    }
+}
+
+/// Splits `text` into words, each with its trailing space, and the pause
+/// before each so that together they take `total`. Text that is empty, e.g.
+/// after post-processing removed everything, gives no words.
+pub fn spread_words(text: &str, total: Duration) -> (Vec<&str>, Duration) {
+    let words: Vec<&str> = text.split_inclusive(' ').collect();
+    let per_word = total / words.len().max(1) as u32;
+    (words, per_word)
+}
+
+/// Queued pieces beyond one tick's worth are worked off this many at a
+/// time per extra tick, so a fast model doesn't leave the UI far behind.
+const CATCH_UP_DIVISOR: usize = 8;
+
+/// Splits streamed tokens into words (each with its trailing whitespace) or
+/// single characters. Joining everything `push` and `finish` return gives
+/// back exactly the tokens pushed.
+pub struct Splitter {
+    granularity: StreamGranularity,
+    pending: String,
+}
+
+impl Splitter {
+    pub fn new(granularity: StreamGranularity) -> Self {
+        Splitter {
+            granularity,
+            pending: String::new(),
+        }
+    }
+
+    /// The pieces completed by `token`. A word is only complete once the
+    /// next one starts, so the last word stays pending.
+    pub fn push(&mut self, token: &str) -> Vec<String> {
+        match self.granularity {
+            StreamGranularity::Token => vec![token.to_string()],
+            StreamGranularity::Char => token.chars().map(String::from).collect(),
+            StreamGranularity::Word => {
+                self.pending.push_str(token);
+                let mut pieces = Vec::new();
+                let mut start = 0;
+                let mut after_space = false;
+                for (i, c) in self.pending.char_indices() {
+                    let space = c.is_whitespace();
+                    if after_space && !space {
+                        pieces.push(self.pending[start..i].to_string());
+                        start = i;
+                    }
+                    after_space = space;
+                }
+                self.pending.drain(..start);
+                pieces
+            }
+        }
+    }
+
+    /// Whatever is left once the stream ends.
+    pub fn finish(&mut self) -> Option<String> {
+        (!self.pending.is_empty()).then(|| std::mem::take(&mut self.pending))
+    }
+}
+
+/// Emits `ai-token` events for a stream at the chosen granularity. With
+/// `Token` each token goes out as soon as it is pushed; otherwise pieces
+/// are queued and sent on `tick`.
+pub struct Pacer {
+    splitter: Splitter,
+    queue: VecDeque<String>,
+    interval: Option<tokio::time::Interval>,
+}
+
+impl Pacer {
+    pub fn new(granularity: StreamGranularity) -> Self {
+        Pacer {
+            splitter: Splitter::new(granularity),
+            queue: VecDeque::new(),
+            interval: granularity.cadence().map(|cadence| {
+                let mut interval = tokio::time::interval(cadence);
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                interval
+            }),
+        }
+    }
+
+    pub fn push(&mut self, window: &tauri::Window, token: &str) -> Result<(), tauri::Error> {
+        if self.interval.is_none() {
+            return window.emit("ai-token", token);
+        }
+        self.queue.extend(self.splitter.push(token));
+        Ok(())
+    }
+
+    pub fn has_queued(&self) -> bool {
+        !self.queue.is_empty()
+    }
+
+    /// Waits for the next slot. Never completes for `Token`, so only poll it
+    /// while `has_queued`.
+    pub async fn tick(&mut self) {
+        match &mut self.interval {
+            Some(interval) => {
+                interval.tick().await;
+            }
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Sends the next queued piece, plus a share of any backlog.
+    pub fn emit_queued(&mut self, window: &tauri::Window) -> Result<(), tauri::Error> {
+        let count = 1 + self.queue.len() / CATCH_UP_DIVISOR;
+        for piece in self.queue.drain(..count.min(self.queue.len())) {
+            window.emit("ai-token", piece)?;
+        }
+        Ok(())
+    }
+
+    /// Flushes what's left at the usual pace, once the stream has ended.
+    pub async fn finish(&mut self, window: &tauri::Window) -> Result<(), tauri::Error> {
+        self.queue.extend(self.splitter.finish());
+        while self.has_queued() {
+            self.tick().await;
+            self.emit_queued(window)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postprocess::{self, PostProcessor};
+
+    #[test]
+    fn spreads_words_over_the_delay() {
+        let (words, pause) = spread_words("[offline] You said: hi", Duration::from_millis(400));
+        assert_eq!(words, ["[offline] ", "You ", "said: ", "hi"]);
+        assert_eq!(pause, Duration::from_millis(100));
+    }
+
+    #[test]
+    fn a_reply_emptied_by_post_processing_has_no_words() {
+        let chain = [PostProcessor::StopAt("[".into())];
+        let reply = postprocess::apply(&chain, "", "[offline] You said: hi".into());
+        let (words, pause) = spread_words(&reply, Duration::from_millis(400));
+        assert!(words.is_empty());
+        assert_eq!(pause, Duration::from_millis(400));
+    }
+}
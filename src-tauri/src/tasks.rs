@@ -0,0 +1,83 @@
+use std::future::Future;
+
+use tauri::Manager;
+
+use crate::error::AppError;
+
+/// What a background task was doing, as reported in `task-complete`.
+#[derive(serde::Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskKind {
+    Batch,
+    Warmup,
+    OutboxFlush,
+    Export,
+}
+
+/// Payload of the `task-complete` event, emitted to every window once a
+/// background task ends either way.
+#[derive(serde::Serialize, Clone)]
+struct TaskComplete {
+    task_id: String,
+    kind: TaskKind,
+    success: bool,
+    /// A short summary on success, the error text on failure.
+    message: String,
+    /// What the task produced, e.g. batch replies or the exported document;
+    /// `null` on failure.
+    result: serde_json::Value,
+}
+
+/// Runs `task` in the background and returns its id straight away. When it
+/// ends, `task-complete` carries the id, `summary` of the output (or the
+/// error) and the output itself.
+pub fn spawn<T, F>(
+    app: &tauri::AppHandle,
+    kind: TaskKind,
+    task: impl FnOnce(String) -> F,
+    summary: impl FnOnce(&T) -> String + Send + 'static,
+) -> String
+where
+    T: serde::Serialize,
+    F: Future<Output = Result<T, AppError>> + Send + 'static,
+{
+    let task_id = uuid::Uuid::new_v4().to_string();
+    let future = task(task_id.clone());
+    let app = app.clone();
+    let id = task_id.clone();
+    tauri::async_runtime::spawn(async move {
+        let result = future.await;
+        notify(&app, id, kind, result.as_ref().map(|output| (summary(output), output)));
+    });
+    task_id
+}
+
+/// Emits `task-complete` for a task that ran on its own schedule rather
+/// than through `spawn`.
+pub fn notify<T: serde::Serialize>(
+    app: &tauri::AppHandle,
+    task_id: String,
+    kind: TaskKind,
+    outcome: Result<(String, &T), &AppError>,
+) {
+    let event = match outcome {
+        Ok((message, output)) => TaskComplete {
+            task_id,
+            kind,
+            success: true,
+            message,
+            result: serde_json::to_value(output).unwrap_or_default(),
+        },
+        Err(e) => TaskComplete {
+            task_id,
+            kind,
+            success: false,
+            message: e.to_string(),
+            result: serde_json::Value::Null,
+        },
+    };
+    tracing::info!(task_id = %event.task_id, kind = ?event.kind, success = event.success, "background task finished");
+    if let Err(e) = app.emit_all("task-complete", event) {
+        tracing::warn!(error = %e, "could not emit task-complete");
+    }
+}
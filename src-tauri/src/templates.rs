@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+
+use crate::error::AppError;
+
+/// Fills every `{{name}}` placeholder in `body` from `vars`. Whitespace
+/// inside the braces is ignored. A placeholder without a value is an error
+/// naming it, so a half-filled prompt is never sent.
+pub fn render(body: &str, vars: &HashMap<String, String>) -> Result<String, AppError> {
+    let mut out = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            return Err(AppError::InvalidParams("template has an unclosed {{ placeholder".into()));
+        };
+        let name = after[..end].trim();
+        let value = vars
+            .get(name)
+            .ok_or_else(|| AppError::InvalidParams(format!("template placeholder {{{{{}}}}} has no value", name)))?;
+        out.push_str(value);
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn fills_placeholders_ignoring_inner_whitespace() {
+        let rendered = render("Hi {{name}}, {{ name }} likes {{  thing\t}}.", &vars(&[("name", "Ada"), ("thing", "tea")])).unwrap();
+        assert_eq!(rendered, "Hi Ada, Ada likes tea.");
+        assert_eq!(render("no placeholders", &vars(&[])).unwrap(), "no placeholders");
+    }
+
+    #[test]
+    fn rejects_an_unclosed_placeholder() {
+        let err = render("Hi {{name", &vars(&[("name", "Ada")])).unwrap_err();
+        assert!(matches!(err, AppError::InvalidParams(ref msg) if msg.contains("unclosed")), "{:?}", err);
+    }
+
+    #[test]
+    fn names_a_placeholder_without_a_value() {
+        let err = render("Hi {{ name }} from {{place}}", &vars(&[("name", "Ada")])).unwrap_err();
+        assert!(matches!(err, AppError::InvalidParams(ref msg) if msg.contains("{{place}}")), "{:?}", err);
+    }
+}
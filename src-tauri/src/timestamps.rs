@@ -0,0 +1,100 @@
+use chrono::{DateTime, Local, SecondsFormat, TimeZone, Utc};
+
+use crate::ChatMessage;
+
+/// Which zone message timestamps are shown in when history is returned.
+/// They are always stored in UTC.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampDisplay {
+    #[default]
+    Local,
+    Utc,
+}
+
+/// The current time as a message timestamp: RFC 3339 in UTC with
+/// milliseconds, e.g. `2024-05-01T09:30:00.250Z`. The fixed width means
+/// these sort as strings in time order, which `ORDER BY timestamp` relies on.
+pub fn now() -> String {
+    to_stored(Utc::now())
+}
+
+fn to_stored(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Reads an RFC 3339 timestamp in any offset, or the epoch milliseconds
+/// messages were stamped with before RFC 3339.
+pub fn parse(timestamp: &str) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(timestamp) {
+        return Some(time.with_timezone(&Utc));
+    }
+    let millis: i64 = timestamp.parse().ok()?;
+    Utc.timestamp_millis_opt(millis).single()
+}
+
+/// `timestamp` in the stored form, or `None` if it can't be parsed.
+pub fn normalize(timestamp: &str) -> Option<String> {
+    parse(timestamp).map(to_stored)
+}
+
+/// A stored timestamp as RFC 3339 in the `display` zone. Unparseable ones
+/// come back unchanged.
+pub fn format_timestamp(timestamp: &str, display: TimestampDisplay) -> String {
+    match (parse(timestamp), display) {
+        (Some(time), TimestampDisplay::Local) => time.with_timezone(&Local).to_rfc3339_opts(SecondsFormat::Millis, true),
+        (Some(time), TimestampDisplay::Utc) => to_stored(time),
+        (None, _) => timestamp.to_string(),
+    }
+}
+
+/// Rewrites the timestamps of messages about to be returned for `display`.
+pub fn localize(messages: &mut [ChatMessage], display: TimestampDisplay) {
+    for message in messages {
+        message.timestamp = format_timestamp(&message.timestamp, display);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn stored_form_sorts_like_time_across_boundaries() {
+        let times = [
+            at("1999-12-31T23:59:59.999Z"),
+            at("2000-01-01T00:00:00Z"),
+            at("2000-01-01T00:00:00.001Z"),
+            at("2000-01-01T00:00:09Z"),
+            at("2000-01-01T00:00:10Z"),
+            // Earlier than the next one although its local date is later.
+            at("2000-01-02T00:30:00+01:00"),
+            at("2000-01-01T23:45:00Z"),
+        ];
+        let mut by_time = times.to_vec();
+        by_time.sort();
+        let mut by_string: Vec<String> = times.iter().copied().map(to_stored).collect();
+        by_string.sort();
+        assert_eq!(by_string, by_time.into_iter().map(to_stored).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn reads_legacy_epoch_millis() {
+        assert_eq!(normalize("946684800001").as_deref(), Some("2000-01-01T00:00:00.001Z"));
+        assert_eq!(normalize("2000-01-01T01:00:00+01:00").as_deref(), Some("2000-01-01T00:00:00.000Z"));
+        assert_eq!(normalize("yesterday"), None);
+    }
+
+    #[test]
+    fn formats_for_display() {
+        let stored = "2000-01-01T00:00:00.000Z";
+        assert_eq!(format_timestamp(stored, TimestampDisplay::Utc), stored);
+        let local = format_timestamp(stored, TimestampDisplay::Local);
+        assert_eq!(parse(&local), parse(stored));
+        assert_eq!(format_timestamp("not a time", TimestampDisplay::Local), "not a time");
+    }
+}
@@ -0,0 +1,24 @@
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+
+/// Counts tokens the way `model` would. OpenAI-family models (including
+/// `gpt2`) use their real BPE tokenizer; anything tiktoken doesn't know falls
+/// back to `estimate_tokens`.
+pub fn count_tokens(text: &str, model: &str) -> usize {
+    let bpe = match get_tokenizer(model) {
+        Some(Tokenizer::O200kBase) => tiktoken_rs::o200k_base_singleton(),
+        Some(Tokenizer::Cl100kBase) => tiktoken_rs::cl100k_base_singleton(),
+        Some(Tokenizer::P50kBase) => tiktoken_rs::p50k_base_singleton(),
+        Some(Tokenizer::P50kEdit) => tiktoken_rs::p50k_edit_singleton(),
+        Some(Tokenizer::R50kBase | Tokenizer::Gpt2) => tiktoken_rs::r50k_base_singleton(),
+        None => return estimate_tokens(text),
+    };
+    let bpe = bpe.lock();
+    bpe.encode_with_special_tokens(text).len()
+}
+
+/// Rough count for unknown tokenizers: about four tokens per three
+/// whitespace-separated words, which is typical for English BPE vocabularies.
+fn estimate_tokens(text: &str) -> usize {
+    let words = text.split_whitespace().count();
+    (words * 4 + 2) / 3
+}
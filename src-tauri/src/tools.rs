@@ -0,0 +1,98 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use serde_json::Value;
+use tauri::Manager;
+
+use crate::data_dir;
+use crate::error::AppError;
+use crate::performance::{self, SortKey};
+
+type ToolFuture = Pin<Box<dyn Future<Output = Result<Value, AppError>> + Send>>;
+
+/// A local action the model can ask for in `generate_with_tools`. Handlers
+/// take the call's `args` object and return a JSON result for the model.
+struct Tool {
+    name: &'static str,
+    /// Shown to the model, including the arguments it takes.
+    description: &'static str,
+    handler: fn(tauri::AppHandle, Value) -> ToolFuture,
+}
+
+const TOOLS: &[Tool] = &[
+    Tool {
+        name: "get_performance_data",
+        description: "current CPU, memory, disk, network and GPU load; no args",
+        handler: performance_data,
+    },
+    Tool {
+        name: "get_top_processes",
+        description: "busiest processes; args: sort_by (\"cpu\" or \"memory\"), limit (default 5)",
+        handler: top_processes,
+    },
+    Tool {
+        name: "get_self_stats",
+        description: "this app's own CPU, memory and uptime; no args",
+        handler: self_stats,
+    },
+    Tool {
+        name: "get_data_dir_space",
+        description: "free and total space where this app stores its data; no args",
+        handler: data_dir_space,
+    },
+];
+
+fn to_json(value: impl serde::Serialize) -> Result<Value, AppError> {
+    serde_json::to_value(value).map_err(|e| AppError::Parse(e.to_string()))
+}
+
+fn performance_data(app: tauri::AppHandle, _args: Value) -> ToolFuture {
+    Box::pin(async move {
+        to_json(performance::get_performance_data(app.state(), app.state(), app.state(), Some(false)).await?)
+    })
+}
+
+#[derive(serde::Deserialize)]
+struct TopProcessesArgs {
+    sort_by: Option<SortKey>,
+    limit: Option<usize>,
+}
+
+fn top_processes(_app: tauri::AppHandle, args: Value) -> ToolFuture {
+    Box::pin(async move {
+        let args: TopProcessesArgs = serde_json::from_value(args).map_err(|e| AppError::InvalidParams(e.to_string()))?;
+        let limit = args.limit.unwrap_or(5).min(50);
+        to_json(performance::get_top_processes(args.sort_by.unwrap_or(SortKey::Cpu), limit).await?)
+    })
+}
+
+fn self_stats(app: tauri::AppHandle, _args: Value) -> ToolFuture {
+    Box::pin(async move { to_json(performance::get_self_stats(app.state()).await?) })
+}
+
+fn data_dir_space(app: tauri::AppHandle, _args: Value) -> ToolFuture {
+    Box::pin(async move { to_json(data_dir::get_data_dir_space(app.state())?) })
+}
+
+/// Tells the model which tools exist and how to call one.
+pub fn instructions() -> String {
+    let mut text = String::from("You can use these tools:\n");
+    for tool in TOOLS {
+        text.push_str(&format!("- {}: {}\n", tool.name, tool.description));
+    }
+    text.push_str(
+        "To use one, reply with only a JSON object {\"tool\": \"<name>\", \"args\": {...}}. You will be given \
+         its result and can then use another tool or give your final answer as plain text.",
+    );
+    text
+}
+
+/// Runs the tool called `name`. `args` of `null` count as no arguments.
+pub async fn dispatch(app: &tauri::AppHandle, name: &str, args: Value) -> Result<Value, AppError> {
+    let tool = TOOLS
+        .iter()
+        .find(|tool| tool.name == name)
+        .ok_or_else(|| AppError::InvalidParams(format!("unknown tool {:?}", name)))?;
+    let args = if args.is_null() { Value::Object(Default::default()) } else { args };
+    (tool.handler)(app.clone(), args).await
+}
@@ -0,0 +1,58 @@
+use std::path::Path;
+
+use tauri::Manager;
+
+use crate::config::ConfigStore;
+use crate::credentials;
+use crate::error::AppError;
+use crate::http::HttpClient;
+use crate::providers::HuggingFace;
+use crate::rate_limit::RateLimiter;
+use crate::retry;
+
+/// The Inference API rejects larger uploads.
+const MAX_AUDIO_BYTES: u64 = 25 * 1024 * 1024;
+
+/// The audio formats speech-recognition models accept, by file extension.
+fn mime_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match extension.as_str() {
+        "flac" => "audio/flac",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" | "oga" => "audio/ogg",
+        "webm" => "audio/webm",
+        "m4a" => "audio/mp4",
+        _ => return None,
+    })
+}
+
+/// Transcribes an audio file with the Hugging Face speech-recognition model
+/// `model` (e.g. `openai/whisper-large-v3`). The text can be sent on as a
+/// prompt.
+#[tauri::command]
+#[tracing::instrument(skip(app), err)]
+pub async fn transcribe_audio(app: tauri::AppHandle, file_path: String, model: String) -> Result<String, AppError> {
+    let path = Path::new(&file_path);
+    let size = std::fs::metadata(path)
+        .map_err(|e| AppError::InvalidParams(format!("cannot read {}: {}", file_path, e)))?
+        .len();
+    let mime_type = mime_type(path).ok_or_else(|| {
+        AppError::InvalidParams(format!("{} is not a supported audio file (flac, mp3, wav, ogg, webm, m4a)", file_path))
+    })?;
+    if size > MAX_AUDIO_BYTES {
+        return Err(AppError::InvalidParams(format!(
+            "{} is {} bytes, more than the {} byte limit",
+            file_path, size, MAX_AUDIO_BYTES
+        )));
+    }
+    let audio = std::fs::read(path)?;
+
+    let http = app.state::<HttpClient>();
+    let provider = HuggingFace::new(http.get()?, &model, credentials::require_token()?);
+    let max_retries = app.state::<ConfigStore>().get()?.max_retries;
+    app.state::<RateLimiter>().acquire().await?;
+    retry::with_backoff(max_retries, || provider.transcribe(&audio, mime_type))
+        .await
+        .map_err(|e| http.explain(e.into()))
+}
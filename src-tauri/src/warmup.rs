@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tauri::Manager;
+
+use crate::credentials;
+use crate::error::AppError;
+use crate::http::HttpClient;
+use crate::providers::HuggingFace;
+use crate::tasks::{self, TaskKind};
+
+/// Gives up on a model that hasn't loaded within this.
+const WARMUP_TIMEOUT: Duration = Duration::from_secs(120);
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Serverless models are unloaded after a while idle, so a warmup is only
+/// trusted for this long.
+const WARM_FOR: Duration = Duration::from_secs(15 * 60);
+
+enum Warmth {
+    Loading,
+    Loaded(Instant),
+}
+
+/// Which models have been warmed up, so repeated selections don't send more
+/// warmup requests.
+#[derive(Default)]
+pub struct WarmModels(Mutex<HashMap<String, Warmth>>);
+
+impl WarmModels {
+    /// Marks `model` as loading unless it is already loading or recently
+    /// loaded. Returns whether the caller should do the warmup.
+    fn begin(&self, model: &str) -> Result<bool, AppError> {
+        let mut models = self.0.lock()?;
+        match models.get(model) {
+            Some(Warmth::Loading) => Ok(false),
+            Some(Warmth::Loaded(at)) if at.elapsed() < WARM_FOR => Ok(false),
+            _ => {
+                models.insert(model.to_string(), Warmth::Loading);
+                Ok(true)
+            }
+        }
+    }
+
+    fn finish(&self, model: &str, loaded: bool) -> Result<(), AppError> {
+        let mut models = self.0.lock()?;
+        if loaded {
+            models.insert(model.to_string(), Warmth::Loaded(Instant::now()));
+        } else {
+            models.remove(model);
+        }
+        Ok(())
+    }
+}
+
+/// Sends minimal requests to a Hugging Face model until it reports loaded,
+/// returning how long that took in milliseconds. A model already warmed (or
+/// being warmed) returns 0 straight away.
+#[tauri::command]
+#[tracing::instrument(skip(app), err)]
+pub async fn warmup_model(app: tauri::AppHandle, model: String) -> Result<u64, AppError> {
+    let warm = app.state::<WarmModels>();
+    if !warm.begin(&model)? {
+        return Ok(0);
+    }
+    let result = poll_until_loaded(&app, &model).await;
+    warm.finish(&model, result.is_ok())?;
+    result
+}
+
+/// `warmup_model` in the background. Returns a task id at once; the time
+/// taken arrives with `task-complete`.
+#[tauri::command]
+#[tracing::instrument(skip(app), err)]
+pub fn start_warmup(app: tauri::AppHandle, model: String) -> Result<String, AppError> {
+    let warmup_app = app.clone();
+    let name = model.clone();
+    Ok(tasks::spawn(
+        &app,
+        TaskKind::Warmup,
+        |_| warmup_model(warmup_app, model),
+        move |ms: &u64| format!("{} is ready ({} ms)", name, ms),
+    ))
+}
+
+async fn poll_until_loaded(app: &tauri::AppHandle, model: &str) -> Result<u64, AppError> {
+    let provider = HuggingFace::new(app.state::<HttpClient>().get()?, model, credentials::require_token()?);
+    let started = Instant::now();
+    loop {
+        let status = provider.probe().await?;
+        if status != reqwest::StatusCode::SERVICE_UNAVAILABLE {
+            if !status.is_success() {
+                return Err(AppError::ApiStatus {
+                    code: status.as_u16(),
+                    body: format!("warmup of {} failed", model),
+                    request_id: None,
+                });
+            }
+            return Ok(started.elapsed().as_millis() as u64);
+        }
+        if started.elapsed() + POLL_INTERVAL > WARMUP_TIMEOUT {
+            return Err(AppError::Timeout);
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
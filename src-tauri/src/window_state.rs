@@ -0,0 +1,96 @@
+use std::path::Path;
+
+use tauri::{Manager, PhysicalPosition, PhysicalSize};
+
+use crate::data_dir::DataDir;
+use crate::error::AppError;
+
+const FILE_NAME: &str = "window_state.json";
+
+/// Outer position and inner size of the main window, in physical pixels.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+struct WindowState {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+/// A monitor's area in physical pixels.
+struct Bounds {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+impl Bounds {
+    fn of(monitor: &tauri::Monitor) -> Self {
+        Bounds {
+            x: monitor.position().x,
+            y: monitor.position().y,
+            width: monitor.size().width,
+            height: monitor.size().height,
+        }
+    }
+
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width as i32 && y < self.y + self.height as i32
+    }
+}
+
+/// Shrinks `state` to fit `bounds` and moves it fully inside them.
+fn fit(state: WindowState, bounds: &Bounds) -> WindowState {
+    let width = state.width.min(bounds.width);
+    let height = state.height.min(bounds.height);
+    WindowState {
+        x: state.x.clamp(bounds.x, bounds.x + (bounds.width - width) as i32),
+        y: state.y.clamp(bounds.y, bounds.y + (bounds.height - height) as i32),
+        width,
+        height,
+    }
+}
+
+/// Applies the saved size and position, if any, to `window`. A position on a
+/// monitor that is no longer connected is moved onto the primary one.
+pub fn restore(window: &tauri::Window, data_dir: &Path) -> Result<(), AppError> {
+    let Ok(text) = std::fs::read_to_string(data_dir.join(FILE_NAME)) else {
+        return Ok(());
+    };
+    let Ok(state) = serde_json::from_str::<WindowState>(&text) else {
+        return Ok(());
+    };
+    let monitors = window.available_monitors()?;
+    let monitor = monitors
+        .iter()
+        .find(|m| Bounds::of(m).contains(state.x, state.y))
+        .cloned()
+        .or(window.primary_monitor()?)
+        .or_else(|| monitors.first().cloned());
+    let state = match monitor {
+        Some(monitor) => fit(state, &Bounds::of(&monitor)),
+        None => state,
+    };
+    window.set_size(PhysicalSize::new(state.width, state.height))?;
+    window.set_position(PhysicalPosition::new(state.x, state.y))?;
+    Ok(())
+}
+
+/// Records the window's current size and position. Minimized and maximized
+/// windows are skipped so restoring brings back the normal geometry.
+pub fn save(window: &tauri::Window) -> Result<(), AppError> {
+    if window.is_minimized()? || window.is_maximized()? {
+        return Ok(());
+    }
+    let position = window.outer_position()?;
+    let size = window.inner_size()?;
+    let state = WindowState {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    };
+    let text = serde_json::to_string(&state).map_err(|e| AppError::Parse(e.to_string()))?;
+    std::fs::write(window.state::<DataDir>().0.join(FILE_NAME), text)?;
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use std::sync::{Mutex, TryLockError};
+use std::time::{Duration, Instant};
+
+use rusqlite::Connection;
+use tauri::async_runtime::JoinHandle;
+use tauri::Manager;
+
+use crate::db::Db;
+use crate::error::AppError;
+
+/// How often queued writes are applied in the background.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+type Job = Box<dyn FnOnce(&Connection) -> rusqlite::Result<()> + Send>;
+
+/// Bookkeeping writes (usage, latency) that don't need to land before a reply
+/// is returned. They are applied in batches by `spawn_flusher` and once more
+/// on shutdown.
+#[derive(Default)]
+pub struct WriteQueue(Mutex<Vec<Job>>);
+
+impl WriteQueue {
+    pub fn enqueue(&self, job: impl FnOnce(&Connection) -> rusqlite::Result<()> + Send + 'static) -> Result<(), AppError> {
+        self.0.lock()?.push(Box::new(job));
+        Ok(())
+    }
+
+    /// Applies everything queued so far, returning how many writes succeeded.
+    pub fn flush(&self, db: &Db) -> Result<usize, AppError> {
+        let jobs = std::mem::take(&mut *self.0.lock()?);
+        if jobs.is_empty() {
+            return Ok(0);
+        }
+        let conn = db.0.lock()?;
+        Ok(apply(&conn, jobs))
+    }
+
+    /// Final flush before exit. Gives up with `AppError::Timeout` if the
+    /// database stays locked for longer than `timeout`, so a stuck write
+    /// can't hang shutdown.
+    pub fn shutdown(&self, db: &Db, timeout: Duration) -> Result<usize, AppError> {
+        let deadline = Instant::now() + timeout;
+        let conn = loop {
+            match db.0.try_lock() {
+                Ok(conn) => break conn,
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(10))
+                }
+                Err(TryLockError::WouldBlock) => return Err(AppError::Timeout),
+                Err(TryLockError::Poisoned(_)) => return Err(AppError::LockPoisoned),
+            }
+        };
+        let jobs = std::mem::take(&mut *self.0.lock()?);
+        Ok(apply(&conn, jobs))
+    }
+}
+
+/// Runs `jobs` in one transaction. A failing write is logged and skipped
+/// rather than rolling back the others.
+fn apply(conn: &Connection, jobs: Vec<Job>) -> usize {
+    let run = || -> rusqlite::Result<usize> {
+        let tx = conn.unchecked_transaction()?;
+        let mut applied = 0;
+        for job in jobs {
+            match job(&tx) {
+                Ok(()) => applied += 1,
+                Err(e) => tracing::warn!(error = %e, "queued write failed"),
+            }
+        }
+        tx.commit()?;
+        Ok(applied)
+    };
+    run().unwrap_or_else(|e| {
+        tracing::warn!(error = %e, "could not commit queued writes");
+        0
+    })
+}
+
+/// Flushes the app's `WriteQueue` every `FLUSH_INTERVAL`.
+pub fn spawn_flusher(app: tauri::AppHandle) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = app.state::<WriteQueue>().flush(&app.state::<Db>()) {
+                tracing::warn!(error = %e, "background flush failed");
+            }
+        }
+    })
+}
+
+/// The background flusher, kept so shutdown can stop it.
+pub struct Flusher(pub JoinHandle<()>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Db {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE t (n INTEGER NOT NULL);").unwrap();
+        Db(Mutex::new(conn))
+    }
+
+    fn count(db: &Db) -> i64 {
+        db.0.lock().unwrap().query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn shutdown_persists_queued_writes() {
+        let db = test_db();
+        let queue = WriteQueue::default();
+        for n in 0..5 {
+            queue
+                .enqueue(move |conn| conn.execute("INSERT INTO t (n) VALUES (?1)", [n]).map(|_| ()))
+                .unwrap();
+        }
+        assert_eq!(count(&db), 0);
+
+        assert_eq!(queue.shutdown(&db, Duration::from_secs(1)).unwrap(), 5);
+        assert_eq!(count(&db), 5);
+        assert_eq!(queue.flush(&db).unwrap(), 0);
+    }
+
+    #[test]
+    fn failed_write_does_not_drop_the_rest() {
+        let db = test_db();
+        let queue = WriteQueue::default();
+        queue.enqueue(|conn| conn.execute("INSERT INTO t (n) VALUES (1)", []).map(|_| ())).unwrap();
+        queue.enqueue(|conn| conn.execute("INSERT INTO missing (n) VALUES (1)", []).map(|_| ())).unwrap();
+        queue.enqueue(|conn| conn.execute("INSERT INTO t (n) VALUES (2)", []).map(|_| ())).unwrap();
+
+        assert_eq!(queue.shutdown(&db, Duration::from_secs(1)).unwrap(), 2);
+        assert_eq!(count(&db), 2);
+    }
+
+    #[test]
+    fn shutdown_gives_up_when_the_database_stays_locked() {
+        let db = test_db();
+        let queue = WriteQueue::default();
+        queue.enqueue(|conn| conn.execute("INSERT INTO t (n) VALUES (1)", []).map(|_| ())).unwrap();
+
+        let _held = db.0.lock().unwrap();
+        let started = Instant::now();
+        assert!(matches!(queue.shutdown(&db, Duration::from_millis(50)), Err(AppError::Timeout)));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}